@@ -1,17 +1,105 @@
 use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
 use std::rc::Rc;
 
 use fltk::{
     app,
     app::MouseButton,
-    draw::{draw_rect, draw_rect_fill, set_draw_color, set_line_style, LineStyle},
-    enums::{Color, Event, FrameType},
+    draw::{draw_image, draw_rect, draw_rect_fill, set_draw_color, set_line_style, LineStyle},
+    enums::{Color, ColorDepth, Event, EventState, FrameType},
     frame::Frame,
     prelude::*,
     surface::ImageSurface,
 };
 
-use crate::field::{Field, Shape};
+use game_of_life::field::{Field, Shape, State};
+use game_of_life::hashlife::{HashLife, StepError};
+use game_of_life::history::History;
+use game_of_life::pattern::{self, custom, macrocell, rle, Pattern, PatternError};
+use game_of_life::rule::Rule;
+use game_of_life::topology::Topology;
+use game_of_life::transform::{self, Transform};
+
+//first line of session files, everything after the field marker is the field itself
+const SESSIONHEADER: &str = "#Session";
+const SESSIONFIELD: &str = "Field:";
+//how strongly the preview of the current shape covers the cells below it
+const GHOSTWEIGHT: f32 = 0.5;
+const GHOSTALIVE: Color = Color::from_rgb(0, 160, 0);
+const GHOSTDEAD: Color = Color::from_rgb(220, 0, 0);
+const GHOSTIGNORED: Color = Color::from_rgb(128, 128, 128);
+//below this the grid lines would cover the cells, so they are left out
+const MINGRIDLINEDIST: i32 = 4;
+//one pixel covers at most 2^MAXZOOMOUT cells in each direction
+const MAXZOOMOUT: u32 = 32;
+//zooming to fit does not make cells bigger than this
+const MAXFITLINEDIST: i32 = 30;
+//brightness of a pixel with a single alive cell when zoomed out, full ones are black
+const SPARSESHADE: f64 = 200.0;
+const SELECTIONCOLOR: Color = Color::from_rgb(0, 90, 255);
+const BORDERCOLOR: Color = Color::from_rgb(255, 140, 0);
+//dying cells of generations rules fade from the first to the last color
+const DYINGFIRST: Color = Color::from_rgb(200, 30, 0);
+const DYINGLAST: Color = Color::from_rgb(255, 220, 170);
+//jumps without hashlife evolve every generation while the window waits, so they are kept short
+const MAXSTEPJUMPEXPONENT: u8 = 10;
+//binary digits of the density of a random fill
+const MASKPRECISION: u32 = 16;
+
+//the cell under a position on the canvas
+fn screen_to_cell(
+    coords: (i32, i32),
+    offset: (i32, i32),
+    linedist: i32,
+    zoomout: u32,
+) -> (isize, isize) {
+    (
+        ((coords.0 + offset.0).div_euclid(linedist) as isize) << zoomout,
+        ((coords.1 + offset.1).div_euclid(linedist) as isize) << zoomout,
+    )
+}
+
+//two opposite corners of a selected rectangle of cells, both included
+type Selection = ((isize, isize), (isize, isize));
+
+//xorshift, good enough for random fills and avoids pulling in a dependency
+struct Random(u64);
+
+impl Random {
+    fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        //the state must never be zero
+        Self(nanos | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    //64 bits that are each set with the given probability, rounded to a multiple of 2^-MASKPRECISION
+    //every random word halves the probability of the bits so far and adds a half if the binary digit of the density is set
+    fn next_mask(&mut self, density: f64) -> u64 {
+        let threshold = (density.clamp(0.0, 1.0) * (1u64 << MASKPRECISION) as f64).round() as u64;
+        if threshold >> MASKPRECISION == 1 {
+            return u64::MAX;
+        }
+        (0..MASKPRECISION).fold(0, |mask, bit| {
+            let random = self.next_u64();
+            if threshold >> bit & 1 == 1 {
+                mask | random
+            } else {
+                mask & random
+            }
+        })
+    }
+}
+
 fltk::widget_extends!(Canvas, Frame, frame);
 pub struct Canvas {
     chunksize: usize,
@@ -22,7 +110,17 @@ pub struct Canvas {
     xoffsetref: Rc<RefCell<i32>>,
     yoffsetref: Rc<RefCell<i32>>,
     linedistref: Rc<RefCell<i32>>,
+    //one pixel covers 2^zoomout cells in each direction, only used when linedist is 1
+    zoomoutref: Rc<RefCell<u32>>,
     shaperef: Rc<RefCell<Option<Shape>>>,
+    //where the upper left corner of the current shape is placed relative to the cursor
+    shapeoffsetref: Rc<RefCell<(isize, isize)>>,
+    //mouse position while it is over the canvas, used to preview the current shape
+    hoverref: Rc<RefCell<Option<(i32, i32)>>>,
+    selectionref: Rc<RefCell<Option<Selection>>>,
+    clipboard: Option<Pattern>,
+    hashlife: HashLife,
+    history: Rc<RefCell<History>>,
 }
 
 impl Canvas {
@@ -38,6 +136,10 @@ impl Canvas {
         let field = Rc::new(RefCell::new(Field::new(chunksize)));
         let drawmode = Rc::new(RefCell::new(true));
         let shaperef = Rc::new(RefCell::new(None));
+        let shapeoffsetref = Rc::new(RefCell::new((0, 0)));
+        let hoverref = Rc::new(RefCell::new(None));
+        let selectionref: Rc<RefCell<Option<Selection>>> = Rc::new(RefCell::new(None));
+        let history = Rc::new(RefCell::new(History::new()));
 
         frame.set_color(Color::White);
         frame.set_frame(FrameType::DownBox);
@@ -45,6 +147,7 @@ impl Canvas {
         let xoffsetref = Rc::new(RefCell::new(xoffset));
         let yoffsetref = Rc::new(RefCell::new(yoffset));
         let linedistref = Rc::new(RefCell::new(linedist));
+        let zoomoutref = Rc::new(RefCell::new(0));
 
         let surf = ImageSurface::new(frame.width(), frame.height(), false);
         let surf = Rc::from(RefCell::from(surf));
@@ -66,11 +169,18 @@ impl Canvas {
             let xoffsetref = xoffsetref.clone();
             let yoffsetref = yoffsetref.clone();
             let linedistref = linedistref.clone();
+            let zoomoutref = zoomoutref.clone();
             let drawmode = drawmode.clone();
             let shaperef = shaperef.clone();
+            let shapeoffsetref = shapeoffsetref.clone();
+            let hoverref = hoverref.clone();
+            let selectionref = selectionref.clone();
+            let mut selecting = false;
+            let history = history.clone();
 
             move |_, ev| {
                 let mut field = field.borrow_mut();
+                let mut history = history.borrow_mut();
 
                 match ev {
                     Event::Push => {
@@ -80,32 +190,47 @@ impl Canvas {
                         if *drawmode.borrow()
                             && app::event_mouse_button() == app::MouseButton::Right
                         {
-                            let xoffset = *xoffsetref.borrow();
-                            let yoffset = *yoffsetref.borrow();
-                            let linedist = *linedistref.borrow();
-
-                            let xmod = (coords.0 + xoffset).rem_euclid(linedist);
-                            let ymod = (coords.1 + yoffset).rem_euclid(linedist);
-
-                            let fieldcoords = (
-                                ((coords.0 + xoffset - xmod) / linedist) as isize,
-                                ((coords.1 + yoffset - ymod) / linedist) as isize,
+                            let fieldcoords = screen_to_cell(
+                                coords,
+                                (*xoffsetref.borrow(), *yoffsetref.borrow()),
+                                *linedistref.borrow(),
+                                *zoomoutref.borrow(),
                             );
+                            //shift starts a selection instead of drawing
+                            if app::event_state().contains(EventState::Shift) {
+                                *selectionref.borrow_mut() = Some((fieldcoords, fieldcoords));
+                                selecting = true;
+                                return true;
+                            }
                             let curshape = &*shaperef.borrow();
+                            history.begin_edit();
                             match curshape {
                                 Some(shape) => {
-                                    field.set_shape_at(fieldcoords, shape);
+                                    let (dx, dy) = *shapeoffsetref.borrow();
+                                    let corner = (fieldcoords.0 + dx, fieldcoords.1 + dy);
+                                    history.set_shape_at(&mut field, corner, shape);
+                                    history.end_edit();
                                 }
                                 None => {
                                     let curval = field.get_cell(fieldcoords.0, fieldcoords.1);
-                                    field.set_cell(fieldcoords, !curval);
+                                    history.set_cell(&mut field, fieldcoords, !curval);
                                     lastsetfieldcoords = fieldcoords;
                                 }
                             }
                         }
                         true
                     }
+                    //fltk only sends move events to widgets that accepted the enter event
+                    Event::Enter | Event::Move => {
+                        *hoverref.borrow_mut() = Some(app::event_coords());
+                        true
+                    }
+                    Event::Leave => {
+                        *hoverref.borrow_mut() = None;
+                        true
+                    }
                     Event::Drag => {
+                        *hoverref.borrow_mut() = Some(app::event_coords());
                         if app::event_mouse_button() == MouseButton::Left {
                             let coords = app::event_coords();
 
@@ -119,27 +244,35 @@ impl Canvas {
 
                             lastclickedcoords = coords;
                             true
+                        } else if app::event_mouse_button() == MouseButton::Right && selecting {
+                            let coords = app::event_coords();
+
+                            let fieldcoords = screen_to_cell(
+                                coords,
+                                (*xoffsetref.borrow(), *yoffsetref.borrow()),
+                                *linedistref.borrow(),
+                                *zoomoutref.borrow(),
+                            );
+                            if let Some((_, corner)) = &mut *selectionref.borrow_mut() {
+                                *corner = fieldcoords;
+                            }
+                            true
                         } else if app::event_mouse_button() == MouseButton::Right
                             && shaperef.borrow().is_none()
                             && *drawmode.borrow()
                         {
                             let coords = app::event_coords();
 
-                            let xoffset = *xoffsetref.borrow();
-                            let yoffset = *yoffsetref.borrow();
-                            let linedist = *linedistref.borrow();
-
-                            let xmod = (coords.0 + xoffset).rem_euclid(linedist);
-                            let ymod = (coords.1 + yoffset).rem_euclid(linedist);
-
-                            let fieldcoords = (
-                                ((coords.0 + xoffset - xmod) / linedist) as isize,
-                                ((coords.1 + yoffset - ymod) / linedist) as isize,
+                            let fieldcoords = screen_to_cell(
+                                coords,
+                                (*xoffsetref.borrow(), *yoffsetref.borrow()),
+                                *linedistref.borrow(),
+                                *zoomoutref.borrow(),
                             );
                             let curval = field.get_cell(fieldcoords.0, fieldcoords.1);
 
                             if fieldcoords != lastsetfieldcoords {
-                                field.set_cell(fieldcoords, !curval);
+                                history.set_cell(&mut field, fieldcoords, !curval);
                                 lastsetfieldcoords = fieldcoords;
                             }
                             true
@@ -153,14 +286,28 @@ impl Canvas {
                         let xoffset = *xoffsetref.borrow();
                         let yoffset = *yoffsetref.borrow();
                         let linedist = *linedistref.borrow();
+                        let zoomout = *zoomoutref.borrow();
 
                         match app::event_dy() {
-                            app::MouseWheel::Up => {
-                                if linedist > 2 {
-                                    *xoffsetref.borrow_mut() -= (coords.0 + xoffset) / linedist;
-                                    *yoffsetref.borrow_mut() -= (coords.1 + yoffset) / linedist;
-                                    (*linedistref.borrow_mut()) -= 1;
-                                }
+                            app::MouseWheel::Up if linedist > 1 => {
+                                *xoffsetref.borrow_mut() -= (coords.0 + xoffset) / linedist;
+                                *yoffsetref.borrow_mut() -= (coords.1 + yoffset) / linedist;
+                                (*linedistref.borrow_mut()) -= 1;
+                            }
+                            //below one pixel per cell every step halves the size, keeping the cell under the mouse in place
+                            app::MouseWheel::Up if zoomout < MAXZOOMOUT => {
+                                *xoffsetref.borrow_mut() =
+                                    (coords.0 + xoffset).div_euclid(2) - coords.0;
+                                *yoffsetref.borrow_mut() =
+                                    (coords.1 + yoffset).div_euclid(2) - coords.1;
+                                *zoomoutref.borrow_mut() += 1;
+                            }
+                            app::MouseWheel::Down if zoomout > 0 => {
+                                *xoffsetref.borrow_mut() =
+                                    (coords.0 + xoffset).saturating_mul(2) - coords.0;
+                                *yoffsetref.borrow_mut() =
+                                    (coords.1 + yoffset).saturating_mul(2) - coords.1;
+                                *zoomoutref.borrow_mut() -= 1;
                             }
                             app::MouseWheel::Down => {
                                 *xoffsetref.borrow_mut() += (coords.0 + xoffset) / linedist;
@@ -171,6 +318,12 @@ impl Canvas {
                         }
                         true
                     }
+                    Event::Released => {
+                        //a drawn stroke gets undone as a whole
+                        history.end_edit();
+                        selecting = false;
+                        true
+                    }
                     _ => false,
                 }
             }
@@ -184,7 +337,14 @@ impl Canvas {
             xoffsetref,
             yoffsetref,
             linedistref,
+            zoomoutref,
             shaperef,
+            shapeoffsetref,
+            hoverref,
+            selectionref,
+            clipboard: None,
+            hashlife: HashLife::new(),
+            history,
         }
     }
 
@@ -199,27 +359,40 @@ impl Canvas {
         ImageSurface::push_current(&self.surf.borrow_mut());
         draw_rect_fill(0, 0, self.w(), self.h(), Color::White);
 
-        set_draw_color(Color::Black);
+        //only the chunks on screen get looked at, the cells are drawn straight from them
+        if linedist >= MINGRIDLINEDIST && self.zoomout() == 0 {
+            let mincell = self.screen_to_cell((0, 0));
+            let maxcell = self.screen_to_cell((self.w(), self.h()));
+            let cells = self.field.borrow().cells_in(mincell, maxcell);
+            let colors = self.state_colors();
 
-        for xcoord in (linedist - xmod..=self.w()).step_by(linedist as usize) {
-            fltk::draw::draw_line(xcoord, 0, xcoord, self.h());
-        }
-        for ycoord in (linedist - ymod..=self.h()).step_by(linedist as usize) {
-            fltk::draw::draw_line(0, ycoord, self.w(), ycoord);
-        }
+            set_draw_color(Color::Black);
 
-        for xcoord in (-xmod..=self.w()).step_by(linedist as usize) {
-            for ycoord in (-ymod..=self.h()).step_by(linedist as usize) {
-                if self.field.borrow().get_cell(
-                    ((xcoord + xoffset) / linedist) as isize,
-                    ((ycoord + yoffset) / linedist) as isize,
-                ) {
-                    draw_rect_fill(xcoord, ycoord, linedist, linedist, Color::Black);
-                }
+            for xcoord in (linedist - xmod..=self.w()).step_by(linedist as usize) {
+                fltk::draw::draw_line(xcoord, 0, xcoord, self.h());
             }
+            for ycoord in (linedist - ymod..=self.h()).step_by(linedist as usize) {
+                fltk::draw::draw_line(0, ycoord, self.w(), ycoord);
+            }
+
+            for ((x, y), state) in cells {
+                draw_rect_fill(
+                    x as i32 * linedist - xoffset,
+                    y as i32 * linedist - yoffset,
+                    linedist,
+                    linedist,
+                    colors[state as usize],
+                );
+            }
+        } else {
+            self.draw_pixels();
         }
 
-        if drawchunks {
+        self.draw_border();
+        self.draw_ghost();
+        self.draw_selection();
+
+        if drawchunks && self.zoomout() == 0 {
             let xoffset = *self.xoffsetref.borrow();
             let yoffset = *self.yoffsetref.borrow();
             let linedist = *self.linedistref.borrow() * self.chunksize as i32; //we treat chunks as cells with size linedist*DIM when drawing
@@ -250,14 +423,241 @@ impl Canvas {
         self.frame.redraw();
     }
 
+    //for tiny cells, setting pixels in a buffer and drawing it at once is much faster than drawing rectangles
+    fn draw_pixels(&self) {
+        let (xoffset, yoffset) = self.offset();
+        let linedist = self.linedist();
+        let zoomout = self.zoomout();
+        let (width, height) = (self.w().max(0) as usize, self.h().max(0) as usize);
+        let mut buf = vec![255u8; width * height * 3];
+
+        let mincell = self.screen_to_cell((0, 0));
+        let maxcell = self.screen_to_cell((self.w(), self.h()));
+        let field = self.field.borrow();
+
+        if zoomout == 0 {
+            let colors: Vec<_> = self
+                .state_colors()
+                .into_iter()
+                .map(|color| {
+                    let (r, g, b) = color.to_rgb();
+                    [r, g, b]
+                })
+                .collect();
+            field.for_each_cell_in(mincell, maxcell, |x, y, state| {
+                let left = x * linedist as isize - xoffset as isize;
+                let top = y * linedist as isize - yoffset as isize;
+                let right = (left + linedist as isize).min(width as isize);
+                let left = left.max(0);
+                if left >= right {
+                    return;
+                }
+                for py in top.max(0)..(top + linedist as isize).min(height as isize) {
+                    let row = py as usize * width;
+                    for pixel in buf[(row + left as usize) * 3..(row + right as usize) * 3]
+                        .chunks_exact_mut(3)
+                    {
+                        pixel.copy_from_slice(&colors[state as usize]);
+                    }
+                }
+            });
+        } else {
+            //several cells share a pixel, the more of them are alive the darker it gets, dying cells are left out
+            let mut counts = vec![0u64; width * height];
+            let maxcell = (
+                maxcell.0 + (1 << zoomout) - 1,
+                maxcell.1 + (1 << zoomout) - 1,
+            );
+            field.for_each_cell_in(mincell, maxcell, |x, y, state| {
+                if state != 1 {
+                    return;
+                }
+                let px = (x >> zoomout) - xoffset as isize;
+                let py = (y >> zoomout) - yoffset as isize;
+                if (0..width as isize).contains(&px) && (0..height as isize).contains(&py) {
+                    counts[py as usize * width + px as usize] += 1;
+                }
+            });
+
+            let cellsperpixel = 4f64.powi(zoomout as i32);
+            for (pixel, count) in buf.chunks_exact_mut(3).zip(counts) {
+                if count > 0 {
+                    let density = count as f64 / cellsperpixel;
+                    pixel.fill((SPARSESHADE * (1.0 - density.sqrt())) as u8);
+                }
+            }
+        }
+        let _ = draw_image(&buf, 0, 0, width as i32, height as i32, ColorDepth::Rgb8);
+    }
+
+    //the current shape where a right click would place it, blended over the cells it would change
+    fn draw_ghost(&self) {
+        let shaperef = self.shaperef.borrow();
+        //when zoomed out the cells are too small to show anything useful
+        let (Some(shape), Some(coords), true, 0) = (
+            &*shaperef,
+            *self.hoverref.borrow(),
+            self.drawmode(),
+            self.zoomout(),
+        ) else {
+            return;
+        };
+
+        let (xoffset, yoffset) = self.offset();
+        let linedist = self.linedist();
+        let cursor = self.screen_to_cell(coords);
+        let (dx, dy) = *self.shapeoffsetref.borrow();
+        let fieldcoords = (cursor.0 + dx, cursor.1 + dy);
+
+        //only the visible part of the shape gets drawn
+        let firstcell = |offset: i32, corner: isize| {
+            (offset.div_euclid(linedist) as isize - corner).max(0) as usize
+        };
+        let (firstx, firsty) = (
+            firstcell(xoffset, fieldcoords.0),
+            firstcell(yoffset, fieldcoords.1),
+        );
+        let (countx, county) = (
+            (self.w() / linedist + 2) as usize,
+            (self.h() / linedist + 2) as usize,
+        );
+
+        let field = self.field.borrow();
+        let colors = self.state_colors();
+        for (x, column) in shape.iter().enumerate().skip(firstx).take(countx) {
+            for (y, item) in column.iter().enumerate().skip(firsty).take(county) {
+                let cellx = fieldcoords.0 + x as isize;
+                let celly = fieldcoords.1 + y as isize;
+                let screenx = cellx as i32 * linedist - xoffset;
+                let screeny = celly as i32 * linedist - yoffset;
+
+                let below = colors[field.get_state((cellx, celly)) as usize];
+                match item {
+                    Some(state) => {
+                        let ghost = if *state != 0 { GHOSTALIVE } else { GHOSTDEAD };
+                        let color = Color::color_average(ghost, below, GHOSTWEIGHT);
+                        draw_rect_fill(screenx + 1, screeny + 1, linedist - 1, linedist - 1, color);
+                    }
+                    //cells that are left unchanged only get a dotted outline
+                    None => {
+                        set_draw_color(GHOSTIGNORED);
+                        set_line_style(LineStyle::Dot, 1);
+                        draw_rect(screenx + 1, screeny + 1, linedist - 1, linedist - 1);
+                        set_line_style(LineStyle::Solid, 0);
+                    }
+                }
+            }
+        }
+    }
+
+    //the color of every state of the current rule, white for dead cells
+    fn state_colors(&self) -> Vec<Color> {
+        let states = self.rule().states().max(2);
+        let mut colors = vec![Color::White, Color::Black];
+        for state in 2..states {
+            let weight = if states > 3 {
+                1.0 - (state - 2) as f32 / (states - 3) as f32
+            } else {
+                1.0
+            };
+            colors.push(Color::color_average(DYINGFIRST, DYINGLAST, weight));
+        }
+        //cells can be older than the last state right after switching to a rule with less states
+        colors.resize(State::MAX as usize + 1, DYINGLAST);
+        colors
+    }
+
+    //the screen rectangle around the cells from min to max, both inclusive
+    fn screen_rect(&self, min: (isize, isize), max: (isize, isize)) -> (i32, i32, i32, i32) {
+        let (xoffset, yoffset) = self.offset();
+
+        //clamped to the visible area, far away corners would overflow the screen coordinates
+        let left = self
+            .cell_to_screen(min.0, xoffset)
+            .clamp(-1, self.w() as isize + 1) as i32;
+        let top = self
+            .cell_to_screen(min.1, yoffset)
+            .clamp(-1, self.h() as isize + 1) as i32;
+        let right = self
+            .cell_to_screen(max.0 + 1, xoffset)
+            .clamp(-1, self.w() as isize + 1) as i32;
+        let bottom = self
+            .cell_to_screen(max.1 + 1, yoffset)
+            .clamp(-1, self.h() as isize + 1) as i32;
+        (left, top, right - left, bottom - top)
+    }
+
+    fn draw_selection(&self) {
+        let Some((min, max)) = self.selection() else {
+            return;
+        };
+        let (x, y, w, h) = self.screen_rect(min, max);
+
+        set_draw_color(SELECTIONCOLOR);
+        set_line_style(LineStyle::Dash, 2);
+        draw_rect(x, y, w, h);
+        set_line_style(LineStyle::Solid, 0);
+    }
+
+    //the edges of a finite universe
+    fn draw_border(&self) {
+        let Some((width, height)) = self.topology().size() else {
+            return;
+        };
+        let (x, y, w, h) = self.screen_rect((0, 0), (width as isize - 1, height as isize - 1));
+
+        set_draw_color(BORDERCOLOR);
+        set_line_style(LineStyle::Solid, 2);
+        draw_rect(x - 1, y - 1, w + 2, h + 2);
+        set_line_style(LineStyle::Solid, 0);
+    }
+
     pub fn update(&mut self) {
-        self.field.borrow_mut().update();
+        let mut field = self.field.borrow_mut();
+        self.history.borrow_mut().save_state(&field);
+        field.update();
+    }
+
+    pub fn update_threaded(&mut self) {
+        let mut field = self.field.borrow_mut();
+        self.history.borrow_mut().save_state(&field);
+        field.update_threaded();
+    }
+
+    //a run is undone as a whole, so only the field before it is saved instead of every generation
+    pub fn start_run(&mut self) {
+        self.history.borrow_mut().save_state(&self.field.borrow());
+    }
+
+    pub fn update_run(&mut self) {
+        self.field.borrow_mut().update_threaded();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.history.borrow_mut().undo(&mut self.field.borrow_mut())
     }
 
-    pub fn update_threaded(&mut self, chunks_per_thread: usize, max_threads: usize) {
-        self.field
-            .borrow_mut()
-            .update_threaded(chunks_per_thread, max_threads);
+    pub fn redo(&mut self) -> bool {
+        self.history.borrow_mut().redo(&mut self.field.borrow_mut())
+    }
+
+    //advances 2^exponent generations at once and returns the exponent it used, the hashlife cache is kept between jumps
+    pub fn jump(&mut self, exponent: u8) -> Result<u8, StepError> {
+        let mut field = self.field.borrow_mut();
+        //hashlife only knows the infinite plane and alive and dead cells
+        if field.topology().is_finite() || field.rule().states() > 2 {
+            self.history.borrow_mut().save_state(&field);
+            let exponent = exponent.min(MAXSTEPJUMPEXPONENT);
+            for _ in 0..1u64 << exponent {
+                field.update_threaded();
+            }
+            return Ok(exponent);
+        }
+        self.hashlife.load_field(&field);
+        self.hashlife.jump(exponent)?;
+        self.history.borrow_mut().save_state(&field);
+        self.hashlife.write_to_field(&mut field);
+        Ok(exponent)
     }
 
     pub fn set_size(&mut self, width: i32, height: i32) {
@@ -290,14 +690,114 @@ impl Canvas {
         *self.linedistref.borrow()
     }
 
+    pub fn set_zoomout(&mut self, val: u32) {
+        *self.zoomoutref.borrow_mut() = val.min(MAXZOOMOUT);
+    }
+
+    pub fn zoomout(&self) -> u32 {
+        *self.zoomoutref.borrow()
+    }
+
+    pub fn screen_to_cell(&self, coords: (i32, i32)) -> (isize, isize) {
+        screen_to_cell(coords, self.offset(), self.linedist(), self.zoomout())
+    }
+
+    //the screen position of the left or upper edge of a cell along one axis
+    fn cell_to_screen(&self, cell: isize, offset: i32) -> isize {
+        (cell >> self.zoomout()) * self.linedist() as isize - offset as isize
+    }
+
+    //shows the whole pattern as big as possible, centered on the canvas
+    pub fn zoom_to_fit(&mut self) {
+        let Some((min, max)) = self.bounding_box() else {
+            return;
+        };
+        let (width, height) = (self.w().max(1) as isize, self.h().max(1) as isize);
+        let size = (max.0 - min.0 + 1, max.1 - min.1 + 1);
+
+        let linedist = (width / size.0)
+            .min(height / size.1)
+            .min(MAXFITLINEDIST as isize);
+        let (linedist, zoomout) = if linedist >= 1 {
+            (linedist as i32, 0)
+        } else {
+            //the smallest power of two that makes the pattern fit
+            let zoomout = (0..MAXZOOMOUT)
+                .find(|zoomout| {
+                    (size.0 + (1 << zoomout) - 1) >> zoomout <= width
+                        && (size.1 + (1 << zoomout) - 1) >> zoomout <= height
+                })
+                .unwrap_or(MAXZOOMOUT);
+            (1, zoomout)
+        };
+        self.set_linedist(linedist);
+        self.set_zoomout(zoomout);
+
+        let centre = ((min.0 + max.0) / 2, (min.1 + max.1) / 2);
+        self.set_offset((
+            ((centre.0 >> zoomout) * linedist as isize + linedist as isize / 2 - width / 2) as i32,
+            ((centre.1 >> zoomout) * linedist as isize + linedist as isize / 2 - height / 2) as i32,
+        ));
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.field.borrow_mut().set_rule(rule);
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.field.borrow().rule()
+    }
+
+    //the cells removed by a smaller universe come back by undoing
+    pub fn set_topology(&mut self, topology: Topology) {
+        let mut field = self.field.borrow_mut();
+        self.history.borrow_mut().save_state(&field);
+        field.set_topology(topology);
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.field.borrow().topology()
+    }
+
     pub fn set_curshape(&mut self, val: Option<Shape>) {
         *self.shaperef.borrow_mut() = val;
+        *self.shapeoffsetref.borrow_mut() = (0, 0);
+    }
+
+    //moves the current shape relative to the cursor, if there is one
+    pub fn move_curshape(&mut self, dx: isize, dy: isize) -> bool {
+        if self.shaperef.borrow().is_none() {
+            return false;
+        }
+        let mut offset = self.shapeoffsetref.borrow_mut();
+        *offset = (offset.0 + dx, offset.1 + dy);
+        true
     }
 
     pub fn get_curshaperef(&self) -> Rc<RefCell<Option<Shape>>> {
         self.shaperef.clone()
     }
 
+    pub fn generation(&self) -> u64 {
+        self.field.borrow().generation()
+    }
+
+    pub fn population(&self) -> u64 {
+        self.field.borrow().population()
+    }
+
+    pub fn births(&self) -> u64 {
+        self.field.borrow().births()
+    }
+
+    pub fn deaths(&self) -> u64 {
+        self.field.borrow().deaths()
+    }
+
+    pub fn bounding_box(&self) -> Option<((isize, isize), (isize, isize))> {
+        self.field.borrow().bounding_box()
+    }
+
     pub fn len(&self) -> usize {
         self.field.borrow().len()
     }
@@ -306,7 +806,315 @@ impl Canvas {
         self.field.borrow().is_empty()
     }
 
+    //replaces the field content, can be undone like clearing
+    pub fn load_pattern(&mut self, pattern: &Pattern) {
+        let mut field = self.field.borrow_mut();
+        self.history.borrow_mut().save_state(&field);
+        pattern.load_into(&mut field);
+    }
+
+    //macrocell files go through hashlife directly, the other formats need the whole bounding box as a shape
+    pub fn open_file(&mut self, path: &Path) -> Result<(), PatternError> {
+        if pattern::is_macrocell(path) {
+            let hashlife = macrocell::read_file(path)?;
+            let mut field = self.field.borrow_mut();
+            self.history.borrow_mut().save_state(&field);
+            hashlife.write_to_field(&mut field);
+        } else {
+            let pattern = pattern::read_file(path)?;
+            self.load_pattern(&pattern);
+        }
+        Ok(())
+    }
+
+    pub fn save_file(&self, path: &Path) -> Result<(), PatternError> {
+        if pattern::is_macrocell(path) {
+            macrocell::write_file(path, &HashLife::from_field(&self.field.borrow()))
+        } else {
+            pattern::write_field(path, &self.field.borrow())
+        }
+    }
+
     pub fn clear(&mut self) {
-        self.field.borrow_mut().clear();
+        let mut field = self.field.borrow_mut();
+        self.history.borrow_mut().save_state(&field);
+        field.clear();
+    }
+
+    //the selected rectangle as upper left and lower right corner
+    pub fn selection(&self) -> Option<Selection> {
+        self.selectionref
+            .borrow()
+            .map(|(a, b)| ((a.0.min(b.0), a.1.min(b.1)), (a.0.max(b.0), a.1.max(b.1))))
+    }
+
+    pub fn set_selection(&mut self, val: Option<Selection>) {
+        *self.selectionref.borrow_mut() = val;
+    }
+
+    //the selected cells go to the clipboard and as rle to the clipboard of the system
+    pub fn copy_selection(&mut self) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let cells = self.field.borrow().cells_in(min, max);
+        let pattern = Pattern {
+            cells: cells
+                .into_iter()
+                .map(|((x, y), state)| (((x - min.0) as usize, (y - min.1) as usize), state))
+                .collect(),
+            size: ((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize),
+            rule: Some(self.rule()),
+            ..Default::default()
+        };
+        app::copy(&rle::to_string(&pattern));
+        self.clipboard = Some(pattern);
+        true
+    }
+
+    pub fn cut_selection(&mut self) -> bool {
+        self.copy_selection() && self.delete_selection()
+    }
+
+    //the clipboard becomes the current shape, so it gets placed with the next right click
+    pub fn paste(&mut self) -> bool {
+        match &self.clipboard {
+            Some(pattern) => {
+                self.set_curshape(Some(pattern.to_shape()));
+                true
+            }
+            None => false,
+        }
     }
-}
\ No newline at end of file
+
+    pub fn delete_selection(&mut self) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        self.replace_selection((min, max), (min, max), Vec::new());
+        true
+    }
+
+    //every selected cell becomes alive with the given probability, a whole row of a chunk at a time, undone as one step
+    pub fn fill_selection_random(&mut self, density: f64) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let mut field = self.field.borrow_mut();
+        let mut random = Random::from_time();
+
+        self.history.borrow_mut().save_state(&field);
+        field.fill_rows(min, max, || random.next_mask(density));
+        true
+    }
+
+    //clears the selected cells and the target and then sets the given cells as one edit, the target becomes the new selection
+    fn replace_selection(
+        &mut self,
+        (min, max): Selection,
+        target: Selection,
+        cells: Vec<((isize, isize), State)>,
+    ) {
+        {
+            let mut field = self.field.borrow_mut();
+            let mut history = self.history.borrow_mut();
+
+            //only the alive and dying cells have to be changed, the selection can be much bigger than the pattern
+            let mut cleared = field.cells_in(min, max);
+            cleared.extend(field.cells_in(target.0, target.1));
+            history.begin_edit();
+            for (coords, _) in cleared {
+                history.set_state(&mut field, coords, 0);
+            }
+            for (coords, state) in cells {
+                history.set_state(&mut field, coords, state);
+            }
+            history.end_edit();
+        }
+        self.set_selection(Some(target));
+    }
+
+    //replace_selection with a shape placed at the corner of the selection
+    fn replace_selection_with_shape(&mut self, (min, max): Selection, shape: &Shape) {
+        let pattern = Pattern::from_shape(shape);
+        if pattern.size.0 == 0 || pattern.size.1 == 0 {
+            return;
+        }
+        let target = (
+            min,
+            (
+                min.0 + pattern.size.0 as isize - 1,
+                min.1 + pattern.size.1 as isize - 1,
+            ),
+        );
+        let cells = pattern
+            .cells
+            .into_iter()
+            .map(|((x, y), state)| ((min.0 + x as isize, min.1 + y as isize), state))
+            .collect();
+        self.replace_selection((min, max), target, cells);
+    }
+
+    pub fn transform_selection(&mut self, transform: Transform) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let shape = transform.apply(&self.field.borrow().extract(min, max));
+        self.replace_selection_with_shape((min, max), &shape);
+        true
+    }
+
+    //only the alive and dying cells get moved, the rest of the selection just clears its new place
+    pub fn move_selection(&mut self, dx: isize, dy: isize) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let cells = self
+            .field
+            .borrow()
+            .cells_in(min, max)
+            .into_iter()
+            .map(|((x, y), state)| ((x + dx, y + dy), state))
+            .collect();
+        let target = ((min.0 + dx, min.1 + dy), (max.0 + dx, max.1 + dy));
+        self.replace_selection((min, max), target, cells);
+        true
+    }
+
+    pub fn invert_selection(&mut self) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let shape = transform::invert(&self.field.borrow().extract(min, max));
+        self.replace_selection_with_shape((min, max), &shape);
+        true
+    }
+
+    //shrinks the selection to the alive and dying cells in it, the cells stay where they are
+    pub fn trim_selection(&mut self) -> bool {
+        let Some((min, max)) = self.selection() else {
+            return false;
+        };
+        let cells = self.field.borrow().cells_in(min, max);
+        let xs = || cells.iter().map(|((x, _), _)| *x);
+        let ys = || cells.iter().map(|((_, y), _)| *y);
+        self.set_selection(xs().min().zip(ys().min()).zip(xs().max().zip(ys().max())));
+        true
+    }
+
+    //applies a function to the current shape, if there is one
+    pub fn map_curshape(&mut self, f: impl FnOnce(&Shape) -> Shape) -> bool {
+        let mut curshape = self.shaperef.borrow_mut();
+        match &mut *curshape {
+            Some(shape) => {
+                *shape = f(shape);
+                true
+            }
+            None => false,
+        }
+    }
+
+    //the field together with the viewport, the selected shape and the update intervall
+    pub fn serialize_session(&self, intervall: f64) -> String {
+        let (xoffset, yoffset) = self.offset();
+        let mut res = format!("{SESSIONHEADER}\n");
+        res.push_str(&format!("Offset = {xoffset} {yoffset}\n"));
+        res.push_str(&format!("Linedist = {}\n", self.linedist()));
+        res.push_str(&format!("Zoomout = {}\n", self.zoomout()));
+        res.push_str(&format!("Intervall = {intervall}\n"));
+        res.push_str(&format!("Topology = {}\n", self.topology()));
+        if let Some(shape) = &*self.shaperef.borrow() {
+            let (dx, dy) = *self.shapeoffsetref.borrow();
+            res.push_str(&format!("ShapeOffset = {dx} {dy}\n"));
+            let pattern = Pattern::from_shape(shape);
+            //every row is prefixed so that leading and trailing spaces survive
+            for row in custom::to_string(&pattern).lines() {
+                res.push_str(&format!("Shape = |{row}\n"));
+            }
+        }
+        res.push_str(&format!("{SESSIONFIELD}\n"));
+        res.push_str(&self.field.borrow().serialize());
+        res
+    }
+
+    //restores a serialized session and returns its update intervall if it has one, the old field can be restored by undoing
+    pub fn deserialize_session(&mut self, text: &str) -> Result<Option<f64>, PatternError> {
+        let (header, field) = text
+            .split_once(&format!("\n{SESSIONFIELD}\n"))
+            .filter(|(header, _)| header.starts_with(SESSIONHEADER))
+            .ok_or_else(|| {
+                PatternError::InvalidHeader(text.lines().next().unwrap_or("").to_string())
+            })?;
+
+        let mut offset = self.offset();
+        let mut linedist = self.linedist();
+        let mut zoomout = 0;
+        let mut intervall = None;
+        let mut topology = Topology::Plane;
+        let mut shaperows = Vec::new();
+        let mut shapeoffset = (0, 0);
+
+        for line in header.lines().skip(1) {
+            let invalid = || PatternError::InvalidHeader(line.to_string());
+            let (key, value) = line.split_once(" = ").ok_or_else(invalid)?;
+            match key {
+                "Offset" => {
+                    let (x, y) = value.split_once(' ').ok_or_else(invalid)?;
+                    offset = (
+                        x.parse().map_err(|_| invalid())?,
+                        y.parse().map_err(|_| invalid())?,
+                    );
+                }
+                "Linedist" => linedist = value.parse().map_err(|_| invalid())?,
+                "Zoomout" => zoomout = value.parse().map_err(|_| invalid())?,
+                "Intervall" => intervall = Some(value.parse().map_err(|_| invalid())?),
+                "Topology" => topology = value.parse()?,
+                "ShapeOffset" => {
+                    let (x, y) = value.split_once(' ').ok_or_else(invalid)?;
+                    shapeoffset = (
+                        x.parse().map_err(|_| invalid())?,
+                        y.parse().map_err(|_| invalid())?,
+                    );
+                }
+                "Shape" => shaperows.push(value.strip_prefix('|').ok_or_else(invalid)?),
+                _ => return Err(invalid()),
+            }
+        }
+        if linedist <= 0 {
+            return Err(PatternError::InvalidHeader(format!(
+                "Linedist = {linedist}"
+            )));
+        }
+
+        let mut newfield = Field::new(self.chunksize);
+        newfield.set_topology(topology);
+        newfield.deserialize(field)?;
+
+        {
+            let mut field = self.field.borrow_mut();
+            self.history.borrow_mut().save_state(&field);
+            field.restore(newfield.state());
+        }
+
+        self.set_offset(offset);
+        //zooming out below one pixel per cell starts at one pixel per cell
+        self.set_linedist(if zoomout > 0 { 1 } else { linedist });
+        self.set_zoomout(zoomout);
+        self.set_curshape(if shaperows.is_empty() {
+            None
+        } else {
+            Some(custom::parse(shaperows.join("\n").as_bytes()).to_shape())
+        });
+        self.move_curshape(shapeoffset.0, shapeoffset.1);
+        Ok(intervall)
+    }
+
+    pub fn save_session(&self, path: &Path, intervall: f64) -> Result<(), PatternError> {
+        fs::write(path, self.serialize_session(intervall))?;
+        Ok(())
+    }
+
+    pub fn open_session(&mut self, path: &Path) -> Result<Option<f64>, PatternError> {
+        self.deserialize_session(&fs::read_to_string(path)?)
+    }
+}
@@ -0,0 +1,15 @@
+use game_of_life::headless;
+use std::env;
+use std::process;
+
+const PROGRAM: &str = "game-of-life-cli";
+
+//the run command of the window binary on its own, so it can be built without fltk
+fn main() {
+    let mut args = env::args_os().skip(1);
+    if args.next().is_some_and(|arg| arg == headless::RUNCOMMAND) {
+        process::exit(headless::run_command(PROGRAM, args));
+    }
+    eprintln!("usage: {PROGRAM} {}", headless::USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,143 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//directories searched for shapes when nothing else is configured, missing ones are ignored
+const DEFAULTSHAPEDIR: &str = "shapes";
+//list of directories in the format of PATH
+pub const SHAPEDIRSENV: &str = "GOL_SHAPE_DIRS";
+pub const SHAPEDIRFLAG: &str = "--shapes";
+const CONFIGFILE: &str = "config";
+const APPNAME: &str = "game-of-life";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    MissingValue(String),
+    UnknownArgument(String),
+    InvalidLine(PathBuf, String),
+    UnknownKey(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingValue(flag) => write!(f, "{flag} needs a value"),
+            ConfigError::UnknownArgument(arg) => write!(f, "unknown argument \"{arg}\""),
+            ConfigError::InvalidLine(path, line) => {
+                write!(f, "{}: invalid line \"{line}\"", path.display())
+            }
+            ConfigError::UnknownKey(path, key) => {
+                write!(f, "{}: unknown key \"{key}\"", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Default, Debug)]
+pub struct Config {
+    pub shapedirs: Vec<PathBuf>,
+}
+
+impl Config {
+    //the default directories, then the ones of the config file, the environment and the command line
+    //invalid lines and arguments are skipped, so the directories of everything else are still used
+    pub fn load() -> (Self, Vec<ConfigError>) {
+        let mut config = Self::defaults();
+        let mut errors = Vec::new();
+
+        if let Some(path) = config_file() {
+            if let Ok(text) = fs::read_to_string(&path) {
+                config.parse_file(&path, &text, &mut errors);
+            }
+        }
+        if let Some(dirs) = env::var_os(SHAPEDIRSENV) {
+            config.add_shapedirs(env::split_paths(&dirs));
+        }
+        config.parse_args(env::args_os().skip(1), &mut errors);
+        (config, errors)
+    }
+
+    //./shapes/ and the shapes next to the executable, so it works from any working directory
+    pub fn defaults() -> Self {
+        let mut config = Self::default();
+        let exedir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(DEFAULTSHAPEDIR)));
+        let dirs = [Some(PathBuf::from(DEFAULTSHAPEDIR)), exedir];
+        config.add_shapedirs(dirs.into_iter().flatten().filter(|dir| dir.is_dir()));
+        config
+    }
+
+    //the same directory given twice would show every shape twice
+    fn add_shapedirs(&mut self, dirs: impl IntoIterator<Item = PathBuf>) {
+        for dir in dirs {
+            let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+            let known = self
+                .shapedirs
+                .iter()
+                .any(|known| known.canonicalize().unwrap_or_else(|_| known.clone()) == canonical);
+            if !known {
+                self.shapedirs.push(dir);
+            }
+        }
+    }
+
+    //lines of the form "shapes = <dir>", relative directories are relative to the config file
+    fn parse_file(&mut self, path: &Path, text: &str, errors: &mut Vec<ConfigError>) {
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                errors.push(ConfigError::InvalidLine(
+                    path.to_path_buf(),
+                    line.to_string(),
+                ));
+                continue;
+            };
+            match key.trim() {
+                "shapes" => self.add_shapedirs([base.join(value.trim())]),
+                key => errors.push(ConfigError::UnknownKey(path.to_path_buf(), key.to_string())),
+            }
+        }
+    }
+
+    //--shapes <dir> and --shapes=<dir>, can be given multiple times
+    fn parse_args(
+        &mut self,
+        mut args: impl Iterator<Item = OsString>,
+        errors: &mut Vec<ConfigError>,
+    ) {
+        while let Some(arg) = args.next() {
+            let text = arg.to_string_lossy();
+            if text == SHAPEDIRFLAG {
+                match args.next() {
+                    Some(dir) => self.add_shapedirs([PathBuf::from(dir)]),
+                    None => errors.push(ConfigError::MissingValue(SHAPEDIRFLAG.to_string())),
+                }
+            } else if let Some(dir) = text.strip_prefix(&format!("{SHAPEDIRFLAG}=")) {
+                self.add_shapedirs([PathBuf::from(dir)]);
+            } else {
+                errors.push(ConfigError::UnknownArgument(text.into_owned()));
+            }
+        }
+    }
+}
+
+//<config dir>/game-of-life/config, with the config dir depending on the platform
+pub fn config_file() -> Option<PathBuf> {
+    let configdir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    configdir.map(|dir| dir.join(APPNAME).join(CONFIGFILE))
+}
@@ -1,14 +1,59 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::hashlife::HashLife;
+use crate::pattern::{macrocell, rle, Format, Pattern, PatternError};
+use crate::pool::WorkerPool;
+use crate::rule::Rule;
+use crate::topology::Topology;
 
 #[derive(Clone)]
 pub struct Square {
     size: usize,
-    cell: Vec<Vec<bool>>,
-    alive_cells: i32,
+    //one bitboard per row, bit x of rows[y] holds the cell (x, y)
+    rows: Vec<u64>,
+    //bit-sliced ages of the dying cells of generations rules, bit b of the age of (x, y) is bit x of ages[b][y]
+    //a dying cell of state s has the age s - 1, there are only as many slices as the oldest cell needs
+    ages: Vec<Vec<u64>>,
+    alive_cells: u32,
+    //whether the chunk changed in the last generation or got edited since
+    changed: bool,
 }
 pub struct Field {
-    pub vec: HashMap<(isize, isize), Square>,
+    pub vec: Arc<HashMap<(isize, isize), Square>>,
+    chunksize: usize,
+    rule: Rule,
+    topology: Topology,
+    //chunks that died out in the last generation or got emptied since
+    vanished: Arc<HashSet<(isize, isize)>>,
+    //created on the first threaded update
+    pool: Option<WorkerPool>,
+    generation: u64,
+    population: u64,
+    //cells born and died during the last generation
+    births: u64,
+    deaths: u64,
+}
+//complete state of a field at one point in time, shares the chunks with the field until it changes
+#[derive(Clone)]
+pub struct FieldState {
+    vec: Arc<HashMap<(isize, isize), Square>>,
+    vanished: Arc<HashSet<(isize, isize)>>,
+    generation: u64,
+    population: u64,
+    births: u64,
+    deaths: u64,
+    rule: Rule,
+    topology: Topology,
+}
+//read only state of a generation, cheap to clone so it can be shared with the worker threads
+#[derive(Clone)]
+struct Snapshot {
+    vec: Arc<HashMap<(isize, isize), Square>>,
+    vanished: Arc<HashSet<(isize, isize)>>,
     chunksize: usize,
+    rule: Rule,
+    topology: Topology,
 }
 enum Direction {
     North,
@@ -47,38 +92,468 @@ impl Direction {
 impl Square {
     fn new(size: usize) -> Self {
         //initializes a square with all cells dead
-        let cells = vec![vec![false; size]; size];
         Self {
             size,
-            cell: cells,
+            rows: vec![0; size],
+            ages: Vec::new(),
             alive_cells: 0,
+            changed: true,
+        }
+    }
+    fn from_rows(size: usize, rows: Vec<u64>) -> Self {
+        let alive_cells = rows.iter().map(|row| row.count_ones()).sum();
+        Self {
+            size,
+            rows,
+            ages: Vec::new(),
+            alive_cells,
+            changed: true,
         }
     }
     fn get_cell(&self, x: usize, y: usize) -> bool {
         assert!((0..self.size).contains(&x));
         assert!((0..self.size).contains(&y));
 
-        self.cell[x][y]
+        self.rows[y] >> x & 1 == 1
+    }
+    fn get_state(&self, x: usize, y: usize) -> State {
+        if self.get_cell(x, y) {
+            return 1;
+        }
+        let age = self
+            .ages
+            .iter()
+            .enumerate()
+            .fold(0, |age, (bit, slice)| age | (slice[y] >> x & 1) << bit);
+        if age == 0 {
+            0
+        } else {
+            age as State + 1
+        }
     }
-    fn set_cell(&mut self, x: usize, y: usize, v: bool) {
+    fn set_state(&mut self, x: usize, y: usize, state: State) {
         assert!((0..self.size).contains(&x));
         assert!((0..self.size).contains(&y));
 
-        if self.cell[x][y] && !v {
+        if self.get_state(x, y) == state {
+            return;
+        }
+        self.changed = true;
+
+        if self.get_cell(x, y) {
             self.alive_cells -= 1;
-        } else if !self.cell[x][y] && v {
-            self.alive_cells += 1;
         }
-        self.cell[x][y] = v;
+        self.rows[y] &= !(1 << x);
+        for slice in self.ages.iter_mut() {
+            slice[y] &= !(1 << x);
+        }
+
+        match state {
+            0 => (),
+            1 => {
+                self.alive_cells += 1;
+                self.rows[y] |= 1 << x;
+            }
+            state => {
+                let age = state - 1;
+                let bits = (u8::BITS - age.leading_zeros()) as usize;
+                if self.ages.len() < bits {
+                    self.ages.resize(bits, vec![0; self.size]);
+                }
+                for (bit, slice) in self.ages.iter_mut().enumerate() {
+                    slice[y] |= ((age >> bit & 1) as u64) << x;
+                }
+            }
+        }
+        self.trim_ages();
+    }
+    //cells of any age are dying
+    fn dying_row(&self, y: usize) -> u64 {
+        self.ages.iter().fold(0, |row, slice| row | slice[y])
+    }
+    fn trim_ages(&mut self) {
+        while self
+            .ages
+            .last()
+            .is_some_and(|slice| slice.iter().all(|row| *row == 0))
+        {
+            self.ages.pop();
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.alive_cells == 0 && self.ages.is_empty()
+    }
+    //kills the cells of the chunk beyond the edges of the universe
+    fn clip(&mut self, coords: (isize, isize), topology: &Topology) {
+        let size = self.size as isize;
+        for y in 0..self.size {
+            let mut outside = 0u64;
+            for x in 0..size {
+                if !topology.contains((coords.0 * size + x, coords.1 * size + y as isize)) {
+                    outside |= 1 << x;
+                }
+            }
+            self.rows[y] &= !outside;
+            for slice in self.ages.iter_mut() {
+                slice[y] &= !outside;
+            }
+        }
+        self.alive_cells = self.rows.iter().map(|row| row.count_ones()).sum();
+        self.trim_ages();
+    }
+    //ages the dying cells of the old generation and lets the cells that stopped being alive start dying
+    fn decay(&mut self, old: &Square, states: State) {
+        let mut ages = old.ages.clone();
+        for y in 0..self.size {
+            let dying = old.dying_row(y);
+            //dying cells can't be born until they are dead
+            self.rows[y] &= !dying;
+
+            let mut carry = dying;
+            for slice in ages.iter_mut() {
+                let next_carry = slice[y] & carry;
+                slice[y] ^= carry;
+                carry = next_carry;
+            }
+            if carry != 0 {
+                let mut slice = vec![0; self.size];
+                slice[y] = carry;
+                ages.push(slice);
+            }
+        }
+
+        //the last state is states - 1, so cells of that age are dead now
+        let limit = states.saturating_sub(1) as u64;
+        for y in 0..self.size {
+            let mut greater = 0;
+            let mut equal = !0;
+            for bit in (0..ages.len().max(8)).rev() {
+                let slice = ages.get(bit).map_or(0, |slice| slice[y]);
+                if limit >> bit & 1 == 1 {
+                    equal &= slice;
+                } else {
+                    greater |= equal & slice;
+                    equal &= !slice;
+                }
+            }
+            let dead = greater | equal;
+            for slice in ages.iter_mut() {
+                slice[y] &= !dead;
+            }
+
+            if states > 2 {
+                let started = old.rows[y] & !self.rows[y];
+                if started != 0 {
+                    if ages.is_empty() {
+                        ages.push(vec![0; self.size]);
+                    }
+                    ages[0][y] |= started;
+                }
+            }
+        }
+        self.ages = ages;
+        self.alive_cells = self.rows.iter().map(|row| row.count_ones()).sum();
+        self.trim_ages();
+    }
+}
+
+//adds a one bit value to every lane of a bit-sliced counter
+fn add_to_counter(counter: &mut [u128; 4], bit: u128) {
+    let mut carry = bit;
+    for digit in counter.iter_mut() {
+        let next_carry = *digit & carry;
+        *digit ^= carry;
+        carry = next_carry;
+    }
+}
+
+//computes the next generation of a row, all rows are padded by one cell on both sides
+fn next_row(rule: &Rule, above: u128, row: u128, below: u128) -> u128 {
+    let mut counter = [0; 4];
+    for neighbors in [above, below] {
+        add_to_counter(&mut counter, neighbors << 1);
+        add_to_counter(&mut counter, neighbors);
+        add_to_counter(&mut counter, neighbors >> 1);
+    }
+    add_to_counter(&mut counter, row << 1);
+    add_to_counter(&mut counter, row >> 1);
+
+    let mut birth = 0;
+    let mut survival = 0;
+    for n in 0..=8u8 {
+        if !rule.birth(n) && !rule.survival(n) {
+            continue;
+        }
+        let has_n_neighbors = counter.iter().enumerate().fold(!0, |mask, (bit, digit)| {
+            mask & if n >> bit & 1 == 1 { *digit } else { !*digit }
+        });
+        if rule.birth(n) {
+            birth |= has_n_neighbors;
+        }
+        if rule.survival(n) {
+            survival |= has_n_neighbors;
+        }
+    }
+    (row & survival) | (!row & birth)
+}
+
+//like next_row, but looks every cell up by its full neighborhood for non-totalistic rules
+fn next_row_isotropic(rule: &Rule, above: u128, row: u128, below: u128, size: usize) -> u128 {
+    (1..=size).fold(0, |res, x| {
+        let neighborhood =
+            (above >> (x - 1) & 7) | (row >> (x - 1) & 7) << 3 | (below >> (x - 1) & 7) << 6;
+        res | (rule.next_cell(neighborhood as u16) as u128) << x
+    })
+}
+
+impl Snapshot {
+    fn get_cell(&self, coords: (isize, isize)) -> bool {
+        let size = self.chunksize as isize;
+        let chunk = (coords.0.div_euclid(size), coords.1.div_euclid(size));
+        self.vec.get(&chunk).is_some_and(|square| {
+            square.get_cell(
+                coords.0.rem_euclid(size) as usize,
+                coords.1.rem_euclid(size) as usize,
+            )
+        })
+    }
+
+    //whether the chunk or the cells around it reach beyond the edges of a finite universe
+    fn is_edge(&self, coords: (isize, isize)) -> bool {
+        let size = self.chunksize as isize;
+        let min = (coords.0 * size - 1, coords.1 * size - 1);
+        let max = (coords.0 * size + size, coords.1 * size + size);
+        !(self.topology.contains(min) && self.topology.contains(max))
+    }
+
+    //a chunk whose whole neighborhood did not change last generation won't change in this one either
+    fn is_settled(&self, coords: (isize, isize)) -> bool {
+        (-1..=1).all(|x| {
+            (-1..=1).all(|y| {
+                let neighbor = (coords.0 + x, coords.1 + y);
+                match self.vec.get(&neighbor) {
+                    Some(square) => !square.changed,
+                    None => !self.vanished.contains(&neighbor),
+                }
+            })
+        })
+    }
+
+    pub fn update_chunk(&self, coords: (isize, isize)) -> Option<Square> {
+        let size = self.chunksize;
+
+        let edge = self.is_edge(coords);
+
+        //the neighbors of edge chunks are somewhere else, so they are always evaluated
+        if !edge && self.is_settled(coords) {
+            return self.vec.get(&coords).map(|square| Square {
+                changed: false,
+                ..square.clone()
+            });
+        }
+
+        //the chunk and its eight neighbors, indexed by [y + 1][x + 1] of their offset
+        let mut neighborhood = [[None; 3]; 3];
+        for (y, line) in neighborhood.iter_mut().enumerate() {
+            for (x, chunk) in line.iter_mut().enumerate() {
+                *chunk = self
+                    .vec
+                    .get(&(coords.0 + x as isize - 1, coords.1 + y as isize - 1));
+            }
+        }
+        if !edge && neighborhood.iter().flatten().all(Option::is_none) {
+            return None;
+        }
+        let row = |x: usize, y: usize, row: usize| {
+            neighborhood[y][x].map_or(0, |square: &Square| square.rows[row] as u128)
+        };
+
+        //rows -1 to size of the chunk, extended by the adjacent column of the west and east neighbor
+        let padded: Vec<u128> = if edge {
+            self.padded_edge_rows(coords)
+        } else {
+            (0..size + 2)
+                .map(|i| {
+                    let (y, cur) = match i {
+                        0 => (0, size - 1),
+                        i if i == size + 1 => (2, 0),
+                        i => (1, i - 1),
+                    };
+                    (row(0, y, cur) >> (size - 1))
+                        | (row(1, y, cur) << 1)
+                        | ((row(2, y, cur) & 1) << (size + 1))
+                })
+                .collect()
+        };
+
+        let mask = (1u128 << size) - 1;
+        let totalistic = self.rule.is_totalistic();
+        let rows = padded
+            .windows(3)
+            .map(|w| {
+                let next = if totalistic {
+                    next_row(&self.rule, w[0], w[1], w[2])
+                } else {
+                    next_row_isotropic(&self.rule, w[0], w[1], w[2], size)
+                };
+                ((next >> 1) & mask) as u64
+            })
+            .collect();
+
+        let mut square = Square::from_rows(size, rows);
+        let states = self.rule.states();
+        if let Some(old) = self.vec.get(&coords) {
+            if states > 2 || !old.ages.is_empty() {
+                square.decay(old, states);
+            }
+        }
+        if edge {
+            square.clip(coords, &self.topology);
+        }
+        square.changed = match self.vec.get(&coords) {
+            Some(old) => old.rows != square.rows || old.ages != square.ages,
+            None => true,
+        };
+        if square.is_empty() {
+            None
+        } else {
+            Some(square)
+        }
+    }
+}
+
+impl Snapshot {
+    //like the padded rows of update_chunk, with every cell looked up through the topology
+    fn padded_edge_rows(&self, coords: (isize, isize)) -> Vec<u128> {
+        let size = self.chunksize as isize;
+        (-1..=size)
+            .map(|y| {
+                (-1..=size).fold(0, |row, x| {
+                    let cell = (coords.0 * size + x, coords.1 * size + y);
+                    match self.topology.wrap(cell) {
+                        Some(cell) if self.get_cell(cell) => row | 1 << (x + 1),
+                        _ => row,
+                    }
+                })
+            })
+            .collect()
     }
 }
 
-pub type Shape = Vec<Vec<Option<bool>>>;
+//0 is dead, 1 alive and the states from 2 on are the dying cells of generations rules
+pub type State = u8;
+pub type Shape = Vec<Vec<Option<State>>>;
 
 impl Field {
     pub fn new(chunksize: usize) -> Self {
-        let vec = HashMap::new();
-        Self { vec, chunksize }
+        assert!(
+            (1..=64).contains(&chunksize),
+            "chunks have to fit into 64 bit rows"
+        );
+        let vec = Arc::new(HashMap::new());
+        Self {
+            vec,
+            chunksize,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            vanished: Arc::new(HashSet::new()),
+            pool: None,
+            generation: 0,
+            population: 0,
+            births: 0,
+            deaths: 0,
+        }
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        //settled chunks are only settled under the old rule
+        for square in Arc::make_mut(&mut self.vec).values_mut() {
+            square.changed = true;
+        }
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    //cells beyond the edges of the new universe are removed
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        let vec = Arc::make_mut(&mut self.vec);
+        for (coords, square) in vec.iter_mut() {
+            if topology.is_finite() {
+                square.clip(*coords, &topology);
+            }
+            //settled chunks are only settled with the old neighbors
+            square.changed = true;
+        }
+
+        let emptied: Vec<_> = vec
+            .iter()
+            .filter(|(_, square)| square.is_empty())
+            .map(|(coords, _)| *coords)
+            .collect();
+        for coords in &emptied {
+            vec.remove(coords);
+        }
+        Arc::make_mut(&mut self.vanished).extend(emptied);
+        self.population = vec.values().map(|square| square.alive_cells as u64).sum();
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn population(&self) -> u64 {
+        self.population
+    }
+
+    pub fn births(&self) -> u64 {
+        self.births
+    }
+
+    pub fn deaths(&self) -> u64 {
+        self.deaths
+    }
+
+    //smallest and largest coordinates of alive cells, both inclusive
+    pub fn bounding_box(&self) -> Option<((isize, isize), (isize, isize))> {
+        let size = self.chunksize as isize;
+        let mut res: Option<((isize, isize), (isize, isize))> = None;
+
+        for (coords, square) in self.vec.iter() {
+            //dying cells count as well
+            let row = |y: usize| square.rows[y] | square.dying_row(y);
+            let columns = (0..square.size).fold(0, |acc, y| acc | row(y));
+            let first_row = (0..square.size).position(|y| row(y) != 0).unwrap();
+            let last_row = (0..square.size).rposition(|y| row(y) != 0).unwrap();
+
+            let min = (
+                coords.0 * size + columns.trailing_zeros() as isize,
+                coords.1 * size + first_row as isize,
+            );
+            let max = (
+                coords.0 * size + 63 - columns.leading_zeros() as isize,
+                coords.1 * size + last_row as isize,
+            );
+            res = Some(match res {
+                Some((curmin, curmax)) => (
+                    (curmin.0.min(min.0), curmin.1.min(min.1)),
+                    (curmax.0.max(max.0), curmax.1.max(max.1)),
+                ),
+                None => (min, max),
+            });
+        }
+        res
     }
 
     pub fn get_cell(&self, x: isize, y: isize) -> bool {
@@ -97,166 +572,358 @@ impl Field {
         }
     }
 
+    pub fn get_state(&self, coords: (isize, isize)) -> State {
+        let size = self.chunksize as isize;
+        let squarecoords = (coords.0.div_euclid(size), coords.1.div_euclid(size));
+        match self.vec.get(&squarecoords) {
+            Some(square) => square.get_state(
+                coords.0.rem_euclid(size) as usize,
+                coords.1.rem_euclid(size) as usize,
+            ),
+            None => 0,
+        }
+    }
+
     pub fn set_cell(&mut self, coords: (isize, isize), val: bool) {
+        self.set_state(coords, val as State);
+    }
+
+    //cells beyond the edges of a finite universe stay dead
+    pub fn set_state(&mut self, coords: (isize, isize), state: State) {
+        if !self.topology.contains(coords) {
+            return;
+        }
+        let size = self.chunksize as isize;
         let localcoords = (
-            coords.0.rem_euclid(self.chunksize as isize),
-            coords.1.rem_euclid(self.chunksize as isize),
-        );
-        let squarecoords = (
-            (coords.0 - localcoords.0) / self.chunksize as isize,
-            (coords.1 - localcoords.1) / self.chunksize as isize,
+            coords.0.rem_euclid(size) as usize,
+            coords.1.rem_euclid(size) as usize,
         );
+        let squarecoords = (coords.0.div_euclid(size), coords.1.div_euclid(size));
+
+        let alive = self.get_cell(coords.0, coords.1);
+        if alive && state != 1 {
+            self.population -= 1;
+        } else if !alive && state == 1 {
+            self.population += 1;
+        }
 
-        match self.vec.get_mut(&squarecoords) {
+        let vec = Arc::make_mut(&mut self.vec);
+        match vec.get_mut(&squarecoords) {
             Some(cursquare) => {
-                cursquare.set_cell(localcoords.0 as usize, localcoords.1 as usize, val);
-                if cursquare.alive_cells == 0 {
-                    self.vec.remove(&squarecoords);
+                cursquare.set_state(localcoords.0, localcoords.1, state);
+                if cursquare.is_empty() {
+                    vec.remove(&squarecoords);
+                    Arc::make_mut(&mut self.vanished).insert(squarecoords);
                 }
             }
             None => {
-                if val {
+                if state != 0 {
                     let mut cursquare = Square::new(self.chunksize);
-                    cursquare.set_cell(localcoords.0 as usize, localcoords.1 as usize, true);
-                    self.vec.insert(squarecoords, cursquare);
+                    cursquare.set_state(localcoords.0, localcoords.1, state);
+                    vec.insert(squarecoords, cursquare);
                 }
             }
         }
     }
 
+    //overwrites the cells between min and max, both inclusive, chunk row by chunk row
+    //bit x of every mask that masks returns is the cell x of the row, bits outside the rectangle are ignored
+    pub fn fill_rows(
+        &mut self,
+        min: (isize, isize),
+        max: (isize, isize),
+        mut masks: impl FnMut() -> u64,
+    ) {
+        let size = self.chunksize as isize;
+        let topology = self.topology;
+        let chunkmin = (min.0.div_euclid(size), min.1.div_euclid(size));
+        let chunkmax = (max.0.div_euclid(size), max.1.div_euclid(size));
+        let vec = Arc::make_mut(&mut self.vec);
+
+        for chunky in chunkmin.1..=chunkmax.1 {
+            let ys = (min.1 - chunky * size).max(0)..=(max.1 - chunky * size).min(size - 1);
+            for chunkx in chunkmin.0..=chunkmax.0 {
+                let firstx = (min.0 - chunkx * size).max(0);
+                let lastx = (max.0 - chunkx * size).min(size - 1);
+                let columns = (u64::MAX >> (63 - lastx)) & (u64::MAX << firstx);
+
+                let coords = (chunkx, chunky);
+                let square = vec
+                    .entry(coords)
+                    .or_insert_with(|| Square::new(self.chunksize));
+                let oldalive = square.alive_cells as u64;
+                for y in ys.clone() {
+                    let y = y as usize;
+                    square.rows[y] = square.rows[y] & !columns | masks() & columns;
+                    for slice in square.ages.iter_mut() {
+                        slice[y] &= !columns;
+                    }
+                }
+                square.changed = true;
+                square.trim_ages();
+                square.alive_cells = square.rows.iter().map(|row| row.count_ones()).sum();
+                if topology.is_finite() {
+                    square.clip(coords, &topology);
+                }
+                self.population = self.population - oldalive + square.alive_cells as u64;
+
+                if square.is_empty() {
+                    vec.remove(&coords);
+                    Arc::make_mut(&mut self.vanished).insert(coords);
+                }
+            }
+        }
+    }
+
+    //the cells between min and max, both inclusive, as a shape that overwrites every cell it covers
+    pub fn extract(&self, min: (isize, isize), max: (isize, isize)) -> Shape {
+        (min.0..=max.0)
+            .map(|x| {
+                (min.1..=max.1)
+                    .map(|y| Some(self.get_state((x, y))))
+                    .collect()
+            })
+            .collect()
+    }
+
     pub fn set_shape_at(&mut self, coords: (isize, isize), shape: &Shape) {
         for (x, line) in shape.iter().enumerate() {
             for (y, item) in line.iter().enumerate() {
-                if let Some(val) = item {
-                    self.set_cell((coords.0 + x as isize, coords.1 + y as isize), *val);
+                if let Some(state) = item {
+                    self.set_state((coords.0 + x as isize, coords.1 + y as isize), *state);
                 }
             }
         }
     }
 
-    fn eval_cells_alive_on_boundary(&self, x: isize, y: isize) -> i32 {
-        //TODO maybe do this via maxdist of x,y values
-        let mut counter = 0;
+    pub fn state(&self) -> FieldState {
+        FieldState {
+            vec: self.vec.clone(),
+            vanished: self.vanished.clone(),
+            generation: self.generation,
+            population: self.population,
+            births: self.births,
+            deaths: self.deaths,
+            rule: self.rule,
+            topology: self.topology,
+        }
+    }
 
-        for dir in Direction::iter() {
-            let coords = Direction::shift((x, y), dir);
-            if self.get_cell(coords.0, coords.1) {
-                counter += 1;
-            }
+    pub fn restore(&mut self, state: FieldState) {
+        self.vec = state.vec;
+        self.vanished = state.vanished;
+        self.generation = state.generation;
+        self.population = state.population;
+        self.births = state.births;
+        self.deaths = state.deaths;
+        self.topology = state.topology;
+        if state.rule != self.rule {
+            //the activity of the chunks was tracked under a different rule
+            self.set_rule(state.rule);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            vec: self.vec.clone(),
+            vanished: self.vanished.clone(),
+            chunksize: self.chunksize,
+            rule: self.rule,
+            topology: self.topology,
         }
-        counter
     }
 
     pub fn update_chunk(&self, coords: (isize, isize)) -> Option<Square> {
-        let cursquare = self.vec.get(&coords);
-        let mut square;
-        let mut checkonlyboundary = false;
+        self.snapshot().update_chunk(coords)
+    }
 
-        match cursquare {
-            Some(squareref) => square = squareref.clone(),
-            None => {
-                square = Square::new(self.chunksize);
-                checkonlyboundary = true;
+    //every chunk that may be alive in the next generation, each one exactly once
+    fn candidates(&self) -> HashSet<(isize, isize)> {
+        let snapshot = self.snapshot();
+        let mut candidates = HashSet::with_capacity(self.vec.len() * 3);
+        for coords in self.vec.keys() {
+            candidates.insert(*coords);
+            if snapshot.is_edge(*coords) {
+                self.insert_wrapped_neighbors(*coords, &mut candidates);
+            } else {
+                for dir in Direction::iter() {
+                    candidates.insert(Direction::shift(*coords, dir));
+                }
+            }
+        }
+        candidates
+    }
+
+    //the chunks the cells around an edge chunk belong to, wherever the topology puts them
+    fn insert_wrapped_neighbors(
+        &self,
+        coords: (isize, isize),
+        candidates: &mut HashSet<(isize, isize)>,
+    ) {
+        let size = self.chunksize as isize;
+        let (left, top) = (coords.0 * size - 1, coords.1 * size - 1);
+        let (right, bottom) = (left + size + 1, top + size + 1);
+        let ring = (left..=right)
+            .flat_map(|x| [(x, top), (x, bottom)])
+            .chain((top..=bottom).flat_map(|y| [(left, y), (right, y)]));
+
+        for cell in ring {
+            if let Some((x, y)) = self.topology.wrap(cell) {
+                candidates.insert((x.div_euclid(size), y.div_euclid(size)));
             }
         }
-        for x in 0..self.chunksize {
-            for y in 0..self.chunksize {
-                if !checkonlyboundary
-                    || x == 0
-                    || y == 0
-                    || x == self.chunksize - 1
-                    || y == self.chunksize - 1
-                {
-                    let curcoord = (
-                        coords.0 * self.chunksize as isize + x as isize,
-                        coords.1 * self.chunksize as isize + y as isize,
-                    );
-
-                    let cells_alive_on_boundary =
-                        self.eval_cells_alive_on_boundary(curcoord.0, curcoord.1);
-                    let curcell = square.get_cell(x, y);
-
-                    if !curcell && cells_alive_on_boundary == 3 {
-                        //a dead cell with 3 neighbors becomes alive
-                        square.set_cell(x, y, true);
-                    } else if curcell
-                        && !(cells_alive_on_boundary == 2 || cells_alive_on_boundary == 3)
-                    {
-                        //an alive cell that does not have 2 or 3 alive neighbors, dies
-                        square.set_cell(x, y, false);
+    }
+
+    fn replace_chunks(&mut self, newvec: HashMap<(isize, isize), Square>) {
+        //only chunks that changed can contain births or deaths
+        let mut births = 0;
+        let mut deaths = 0;
+        for (coords, square) in newvec.iter().filter(|(_, square)| square.changed) {
+            match self.vec.get(coords) {
+                Some(old) => {
+                    for (oldrow, row) in old.rows.iter().zip(&square.rows) {
+                        births += (row & !oldrow).count_ones() as u64;
+                        deaths += (oldrow & !row).count_ones() as u64;
                     }
                 }
+                None => births += square.alive_cells as u64,
             }
         }
-        if square.alive_cells == 0 {
-            None
-        } else {
-            Some(square)
+        for (coords, square) in self.vec.iter() {
+            if !newvec.contains_key(coords) {
+                deaths += square.alive_cells as u64;
+            }
         }
-    }
+        self.births = births;
+        self.deaths = deaths;
+        self.population = self.population + births - deaths;
+        self.generation += 1;
 
-    fn insert_valid_only(
-        key: (isize, isize),
-        elem: Option<Square>,
-        hs: &mut HashMap<(isize, isize), Square>,
-    ) {
-        if let Some(value) = elem {
-            hs.entry(key).or_insert(value);
-        }
+        self.vanished = Arc::new(
+            self.vec
+                .keys()
+                .filter(|coords| !newvec.contains_key(coords))
+                .copied()
+                .collect(),
+        );
+        self.vec = Arc::new(newvec);
     }
 
     pub fn update(&mut self) {
-        let allkeys = self.vec.clone().into_keys();
-        self.vec = self.update_keys(allkeys);
+        let newvec = self.update_keys(self.candidates());
+        self.replace_chunks(newvec);
     }
 
+    //evaluates exactly the given chunks, neighbors of them are not included
     pub fn update_keys<T: IntoIterator<Item = (isize, isize)>>(
         &self,
         keys: T,
     ) -> HashMap<(isize, isize), Square> {
+        let snapshot = self.snapshot();
         let mut hs = HashMap::new();
 
-        for (x, y) in keys {
-            let newchunk = self.update_chunk((x, y));
-            Self::insert_valid_only((x, y), newchunk, &mut hs);
-
-            for dir in Direction::iter() {
-                let coords = Direction::shift((x, y), dir);
-                let newchunk = self.update_chunk(coords);
-                Self::insert_valid_only(coords, newchunk, &mut hs);
+        for coords in keys {
+            if let Some(newchunk) = snapshot.update_chunk(coords) {
+                hs.insert(coords, newchunk);
             }
         }
         hs
     }
-    pub fn update_threaded(&mut self, chunks_per_thread: usize, max_threads: usize) {
-        let allkeys = self.vec.clone().into_keys();
+    //same result as update, with the chunks evaluated by a pool of worker threads
+    pub fn update_threaded(&mut self) {
+        let snapshot = self.snapshot();
+        let allkeys: Vec<_> = self.candidates().into_iter().collect();
 
-        use std::cmp::{max, min};
-        let threadnum = min(
-            max(allkeys.size_hint().0 / chunks_per_thread, 1),
-            max_threads,
-        );
+        let pool = self
+            .pool
+            .get_or_insert_with(WorkerPool::with_available_parallelism);
+        let res = pool.map(allkeys, move |coords| {
+            (*coords, snapshot.update_chunk(*coords))
+        });
 
-        let mut splitkeysvec = vec![Vec::new(); threadnum];
-        for (i, x) in allkeys.enumerate() {
-            splitkeysvec.get_mut(i % threadnum).unwrap().push(x);
-        }
+        let newvec = res
+            .into_iter()
+            .filter_map(|(coords, square)| Some((coords, square?)))
+            .collect();
+        self.replace_chunks(newvec);
+    }
 
+    pub fn alive_cells(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
+        let size = self.chunksize;
+        self.vec.iter().flat_map(move |(coords, square)| {
+            (0..size).flat_map(move |x| {
+                (0..size)
+                    .filter(move |y| square.get_cell(x, *y))
+                    .map(move |y| {
+                        (
+                            coords.0 * size as isize + x as isize,
+                            coords.1 * size as isize + y as isize,
+                        )
+                    })
+            })
+        })
+    }
+
+    //alive and dying cells within the rectangle with their state, only the chunks overlapping it are looked at
+    pub fn cells_in(
+        &self,
+        min: (isize, isize),
+        max: (isize, isize),
+    ) -> Vec<((isize, isize), State)> {
         let mut res = Vec::new();
+        self.for_each_cell_in(min, max, |x, y, state| res.push(((x, y), state)));
+        res
+    }
 
-        use std::thread::scope;
-        scope(|s| {
-            let mut handlevec = Vec::new();
+    //like cells_in, without collecting the cells first
+    pub fn for_each_cell_in(
+        &self,
+        min: (isize, isize),
+        max: (isize, isize),
+        mut f: impl FnMut(isize, isize, State),
+    ) {
+        let size = self.chunksize as isize;
+        let chunkmin = (min.0.div_euclid(size), min.1.div_euclid(size));
+        let chunkmax = (max.0.div_euclid(size), max.1.div_euclid(size));
+        let slots = (chunkmax.0 - chunkmin.0 + 1).saturating_mul(chunkmax.1 - chunkmin.1 + 1);
 
-            for x in splitkeysvec {
-                handlevec.push(s.spawn(|| self.update_keys(x)));
-            }
-            res = handlevec.into_iter().map(|x| x.join().unwrap()).collect();
-        });
+        //looking up every chunk of a huge rectangle would be slower than going through all of them
+        let squares: Vec<(&(isize, isize), &Square)> = if slots < self.vec.len() as isize {
+            (chunkmin.0..=chunkmax.0)
+                .flat_map(|x| (chunkmin.1..=chunkmax.1).map(move |y| (x, y)))
+                .filter_map(|coords| self.vec.get_key_value(&coords))
+                .collect()
+        } else {
+            self.vec
+                .iter()
+                .filter(|(coords, _)| {
+                    (chunkmin.0..=chunkmax.0).contains(&coords.0)
+                        && (chunkmin.1..=chunkmax.1).contains(&coords.1)
+                })
+                .collect()
+        };
 
-        self.vec.clear();
-        for x in res {
-            self.vec.extend(x);
+        for (coords, square) in squares {
+            for (y, alive) in square.rows.iter().enumerate() {
+                let celly = coords.1 * size + y as isize;
+                if !(min.1..=max.1).contains(&celly) {
+                    continue;
+                }
+                let mut row = alive | square.dying_row(y);
+                while row != 0 {
+                    let x = row.trailing_zeros() as usize;
+                    let cellx = coords.0 * size + x as isize;
+                    if (min.0..=max.0).contains(&cellx) {
+                        let state = if alive >> x & 1 == 1 {
+                            1
+                        } else {
+                            square.get_state(x, y)
+                        };
+                        f(cellx, celly, state);
+                    }
+                    row &= row - 1;
+                }
+            }
         }
     }
 
@@ -268,7 +935,255 @@ impl Field {
         self.vec.is_empty()
     }
 
+    //the cells, rule and generation as a macrocell file, which stays small for huge and sparse fields
+    //hashlife only knows alive and dead cells, so dying cells need an rle file
+    pub fn serialize(&self) -> String {
+        if self.vec.values().any(|square| !square.ages.is_empty()) {
+            rle::to_string(&Pattern::from_field(self))
+        } else {
+            macrocell::to_string(&HashLife::from_field(self))
+        }
+    }
+
+    //replaces the content of the field with a serialized one
+    pub fn deserialize(&mut self, text: &str) -> Result<(), PatternError> {
+        match Format::sniff(text) {
+            Format::Macrocell => macrocell::parse(text)?.write_to_field(self),
+            format => format.parse(text.as_bytes())?.load_into(self),
+        }
+        Ok(())
+    }
+
     pub fn clear(&mut self) {
-        self.vec.clear();
+        self.vec = Arc::new(HashMap::new());
+        self.vanished = Arc::new(HashSet::new());
+        self.generation = 0;
+        self.population = 0;
+        self.births = 0;
+        self.deaths = 0;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Cells = HashMap<(isize, isize), State>;
+
+    //xorshift, so the soups are the same on every run
+    fn soup(seed: u64, min: (isize, isize), max: (isize, isize), states: State) -> Cells {
+        let mut seed = seed;
+        let mut cells = HashMap::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                if seed.is_multiple_of(3) {
+                    cells.insert((x, y), (seed / 3 % states as u64) as State);
+                }
+            }
+        }
+        cells.retain(|_, state| *state != 0);
+        cells
+    }
+
+    //evolves every cell on its own, looking its neighbors up through the topology
+    fn naive_step(cells: &Cells, rule: &Rule, topology: &Topology) -> Cells {
+        let candidates: HashSet<(isize, isize)> = match topology.size() {
+            Some((w, h)) => (0..w as isize)
+                .flat_map(|x| (0..h as isize).map(move |y| (x, y)))
+                .collect(),
+            None => cells
+                .keys()
+                .flat_map(|(x, y)| {
+                    (-1..=1).flat_map(move |dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                })
+                .collect(),
+        };
+        let mut res = HashMap::new();
+        for (x, y) in candidates {
+            let state = cells.get(&(x, y)).copied().unwrap_or(0);
+            let mut neighborhood = 0;
+            for (bit, (dx, dy)) in (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .enumerate()
+            {
+                let alive = if (dx, dy) == (0, 0) {
+                    state == 1
+                } else {
+                    topology
+                        .wrap((x + dx, y + dy))
+                        .is_some_and(|coords| cells.get(&coords) == Some(&1))
+                };
+                neighborhood |= (alive as u16) << bit;
+            }
+            let next = match state {
+                0 | 1 if rule.next_cell(neighborhood) => 1,
+                1 if rule.states() > 2 => 2,
+                state if state > 1 && state + 1 < rule.states() => state + 1,
+                _ => 0,
+            };
+            if next != 0 {
+                res.insert((x, y), next);
+            }
+        }
+        res
+    }
+
+    fn cells_of(field: &Field) -> Cells {
+        match field.bounding_box() {
+            Some((min, max)) => field.cells_in(min, max).into_iter().collect(),
+            None => HashMap::new(),
+        }
+    }
+
+    fn check_against_naive(rule: &str, topology: Topology, cells: Cells, gens: usize) {
+        let rule: Rule = rule.parse().unwrap();
+        for chunksize in [3, 8, 64] {
+            let mut field = Field::new(chunksize);
+            field.set_rule(rule);
+            field.set_topology(topology);
+            for (coords, state) in &cells {
+                field.set_state(*coords, *state);
+            }
+            let mut expected = cells.clone();
+            for gen in 0..gens {
+                assert_eq!(
+                    cells_of(&field),
+                    expected,
+                    "{rule} {topology} chunk size {chunksize} generation {gen}"
+                );
+                expected = naive_step(&expected, &rule, &topology);
+                if gen % 2 == 0 {
+                    field.update();
+                } else {
+                    field.update_threaded();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn update_matches_naive_step() {
+        for rule in ["B3/S23", "B36/S23", "B2/S", "B3678/S34678"] {
+            check_against_naive(rule, Topology::Plane, soup(1, (-12, -10), (12, 10), 2), 30);
+        }
+    }
+
+    #[test]
+    fn update_matches_naive_step_in_finite_universes() {
+        for (w, h) in [(13, 10), (16, 16), (5, 3), (1, 1)] {
+            for topology in [
+                Topology::Torus(w, h),
+                Topology::Bounded(w, h),
+                Topology::KleinBottle(w, h, false),
+                Topology::KleinBottle(w, h, true),
+                Topology::CrossSurface(w, h),
+            ] {
+                let cells = soup(
+                    w as u64 * h as u64,
+                    (0, 0),
+                    (w as isize - 1, h as isize - 1),
+                    2,
+                );
+                check_against_naive("B3/S23", topology, cells, 20);
+            }
+        }
+    }
+
+    #[test]
+    fn generations_match_naive_step() {
+        for rule in ["B2/S/C3", "345/2/4", "B3/S23/C8", "B2-a/S12/C4"] {
+            let states = rule.parse::<Rule>().unwrap().states();
+            check_against_naive(
+                rule,
+                Topology::Plane,
+                soup(2, (-10, -8), (10, 8), states),
+                25,
+            );
+            let cells = soup(3, (0, 0), (12, 10), states);
+            check_against_naive(rule, Topology::Torus(13, 11), cells, 25);
+        }
+    }
+
+    #[test]
+    fn update_threaded_matches_update() {
+        fn is_sync<T: Sync>() {}
+        is_sync::<Field>();
+
+        for rule in ["B3/S23", "B36/S23/C5"] {
+            let rule: Rule = rule.parse().unwrap();
+            let mut single = Field::new(8);
+            single.set_rule(rule);
+            for (coords, state) in soup(4, (-150, -100), (150, 100), rule.states()) {
+                single.set_state(coords, state);
+            }
+            let mut threaded = Field::new(8);
+            threaded.restore(single.state());
+            //enough threads and chunks for the work to be spread, whatever the machine
+            threaded.pool = Some(WorkerPool::new(4));
+            for gen in 0..10 {
+                single.update();
+                threaded.update_threaded();
+                assert_eq!(
+                    cells_of(&threaded),
+                    cells_of(&single),
+                    "{rule} generation {gen}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fill_rows_overwrites_only_the_rectangle() {
+        let rule: Rule = "B2/S/C4".parse().unwrap();
+        for topology in [Topology::Plane, Topology::Torus(20, 12)] {
+            for chunksize in [3, 8, 64] {
+                let mut field = Field::new(chunksize);
+                field.set_rule(rule);
+                field.set_topology(topology);
+                let cells = soup(5, (-10, -10), (30, 30), rule.states());
+                for (coords, state) in &cells {
+                    field.set_state(*coords, *state);
+                }
+                //every other cell of a row, counted from the left edge of its chunk
+                let (min, max) = ((-4, 2), (13, 9));
+                field.fill_rows(min, max, || 0x5555_5555_5555_5555);
+
+                let mut expected: Cells = cells
+                    .into_iter()
+                    .filter(|(coords, _)| topology.contains(*coords))
+                    .filter(|((x, y), _)| {
+                        !((min.0..=max.0).contains(x) && (min.1..=max.1).contains(y))
+                    })
+                    .collect();
+                for x in min.0..=max.0 {
+                    for y in min.1..=max.1 {
+                        if x.rem_euclid(chunksize as isize) % 2 == 0 && topology.contains((x, y)) {
+                            expected.insert((x, y), 1);
+                        }
+                    }
+                }
+                assert_eq!(
+                    cells_of(&field),
+                    expected,
+                    "{topology} chunk size {chunksize}"
+                );
+                let alive = expected.values().filter(|state| **state == 1).count();
+                assert_eq!(field.population(), alive as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn cells_outside_of_a_finite_universe_are_removed() {
+        let mut field = Field::new(8);
+        field.set_cell((-1, 0), true);
+        field.set_cell((3, 3), true);
+        field.set_topology(Topology::Torus(4, 4));
+        assert_eq!(cells_of(&field), HashMap::from([((3, 3), 1)]));
+        field.set_cell((4, 0), true);
+        assert_eq!(field.population(), 1);
+    }
+}
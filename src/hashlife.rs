@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::field::{Field, Shape};
+use crate::rule::Rule;
+
+pub(crate) type NodeId = u32;
+
+//level 0 nodes are single cells
+pub(crate) const DEAD: NodeId = 0;
+pub(crate) const ALIVE: NodeId = 1;
+//the root may not grow beyond this, otherwise cell coordinates would not fit into an isize
+pub(crate) const MAXLEVEL: u8 = 62;
+//amount of nodes after which unreachable nodes and cached results get thrown away
+const GCTHRESHOLD: usize = 1 << 22;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StepError {
+    //2^exponent generations don't fit into the largest node
+    ExponentTooLarge(u8),
+    //the pattern would grow beyond the coordinates an isize can hold
+    OutOfRange,
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepError::ExponentTooLarge(exponent) => write!(
+                f,
+                "jump exponent {exponent} is too large, at most {} is possible",
+                MAXLEVEL - 4
+            ),
+            StepError::OutOfRange => write!(f, "the pattern grew out of range"),
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    //nw, ne, sw, se where north is the low y half and west the low x half
+    children: [NodeId; 4],
+    population: u64,
+}
+
+//quadtree based field with memoized evolution, can advance 2^n generations at once
+pub struct HashLife {
+    nodes: Vec<Node>,
+    index: HashMap<[NodeId; 4], NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>,
+    empty: Vec<NodeId>,
+    root: NodeId,
+    rule: Rule,
+    generation: u64,
+}
+
+impl HashLife {
+    pub fn new() -> Self {
+        let leaf = |population| Node {
+            level: 0,
+            children: [DEAD; 4],
+            population,
+        };
+        let mut hashlife = Self {
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            rule: Rule::default(),
+            generation: 0,
+        };
+        hashlife.root = hashlife.empty(3);
+        hashlife
+    }
+
+    pub fn from_field(field: &Field) -> Self {
+        let mut hashlife = Self::new();
+        hashlife.load_field(field);
+        hashlife
+    }
+
+    //replaces the current pattern, rule and generation with the ones of the field, cached results are kept
+    pub fn load_field(&mut self, field: &Field) {
+        self.set_rule(field.rule());
+        self.clear();
+        self.generation = field.generation();
+        for coords in field.alive_cells() {
+            self.set_cell(coords, true);
+        }
+    }
+
+    pub fn write_to_field(&self, field: &mut Field) {
+        field.clear();
+        field.set_rule(self.rule);
+        field.set_generation(self.generation);
+        for coords in self.alive_cells() {
+            field.set_cell(coords, true);
+        }
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        if rule != self.rule {
+            self.rule = rule;
+            self.results.clear();
+        }
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    fn node(&self, id: NodeId) -> Node {
+        self.nodes[id as usize]
+    }
+
+    pub(crate) fn level(&self, id: NodeId) -> u8 {
+        self.node(id).level
+    }
+
+    pub(crate) fn children(&self, id: NodeId) -> [NodeId; 4] {
+        self.node(id).children
+    }
+
+    pub(crate) fn population_of(&self, id: NodeId) -> u64 {
+        self.node(id).population
+    }
+
+    pub(crate) fn root(&self) -> NodeId {
+        self.root
+    }
+
+    //the root is centered around (0, 0) and has to be at least of level 3
+    pub(crate) fn set_root(&mut self, id: NodeId) {
+        let mut root = id;
+        while self.level(root) < 3 {
+            root = self.expand(root);
+        }
+        self.root = root;
+    }
+
+    pub(crate) fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(id) = self.index.get(&children) {
+            return *id;
+        }
+        let level = self.node(children[0]).level + 1;
+        let population = children.iter().map(|c| self.node(*c).population).sum();
+
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            level,
+            children,
+            population,
+        });
+        self.index.insert(children, id);
+        id
+    }
+
+    pub(crate) fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let last = *self.empty.last().unwrap();
+            let next = self.join([last; 4]);
+            self.empty.push(next);
+        }
+        self.empty[level as usize]
+    }
+
+    fn half_width(&self) -> isize {
+        1 << (self.node(self.root).level - 1)
+    }
+
+    fn contains(&self, (x, y): (isize, isize)) -> bool {
+        let half = self.half_width();
+        (-half..half).contains(&x) && (-half..half).contains(&y)
+    }
+
+    //grows the node by one level, keeping the old node in the center
+    fn expand(&mut self, id: NodeId) -> NodeId {
+        let node = self.node(id);
+        let e = self.empty(node.level - 1);
+        let [nw, ne, sw, se] = node.children;
+
+        let nw = self.join([e, e, e, nw]);
+        let ne = self.join([e, e, ne, e]);
+        let sw = self.join([e, sw, e, e]);
+        let se = self.join([se, e, e, e]);
+        self.join([nw, ne, sw, se])
+    }
+
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.node(id).children;
+        let children = [
+            self.node(nw).children[3],
+            self.node(ne).children[2],
+            self.node(sw).children[1],
+            self.node(se).children[0],
+        ];
+        self.join(children)
+    }
+
+    //true if all alive cells lie within the central half of the node
+    fn is_padded(&self, id: NodeId) -> bool {
+        let node = self.node(id);
+        let [nw, ne, sw, se] = node.children;
+        let inner = self.node(self.node(nw).children[3]).population
+            + self.node(self.node(ne).children[2]).population
+            + self.node(self.node(sw).children[1]).population
+            + self.node(self.node(se).children[0]).population;
+        inner == node.population
+    }
+
+    pub fn get_cell(&self, x: isize, y: isize) -> bool {
+        if !self.contains((x, y)) {
+            return false;
+        }
+        let half = self.half_width();
+        let (mut x, mut y) = ((x + half) as usize, (y + half) as usize);
+        let mut id = self.root;
+
+        while self.node(id).level > 0 {
+            let half = 1 << (self.node(id).level - 1);
+            let quadrant = (x >= half) as usize + 2 * (y >= half) as usize;
+            id = self.node(id).children[quadrant];
+            x %= half;
+            y %= half;
+        }
+        id == ALIVE
+    }
+
+    fn set_cell_in(&mut self, id: NodeId, x: usize, y: usize, val: bool) -> NodeId {
+        let node = self.node(id);
+        if node.level == 0 {
+            return if val { ALIVE } else { DEAD };
+        }
+        let half = 1 << (node.level - 1);
+        let quadrant = (x >= half) as usize + 2 * (y >= half) as usize;
+
+        let mut children = node.children;
+        children[quadrant] = self.set_cell_in(children[quadrant], x % half, y % half, val);
+        self.join(children)
+    }
+
+    pub fn set_cell(&mut self, coords: (isize, isize), val: bool) {
+        while !self.contains(coords) {
+            if !val {
+                return;
+            }
+            assert!(
+                self.node(self.root).level < MAXLEVEL,
+                "cell coordinates out of range"
+            );
+            self.root = self.expand(self.root);
+        }
+        let half = self.half_width();
+        self.root = self.set_cell_in(
+            self.root,
+            (coords.0 + half) as usize,
+            (coords.1 + half) as usize,
+            val,
+        );
+    }
+
+    //hashlife only knows alive and dead cells, dying ones are dead here
+    pub fn set_shape_at(&mut self, coords: (isize, isize), shape: &Shape) {
+        for (x, line) in shape.iter().enumerate() {
+            for (y, item) in line.iter().enumerate() {
+                if let Some(state) = item {
+                    self.set_cell((coords.0 + x as isize, coords.1 + y as isize), *state == 1);
+                }
+            }
+        }
+    }
+
+    //evaluates the central 2x2 cells of a 4x4 node one generation ahead
+    fn step_leaves(&mut self, id: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let quadrant = (x >= 2) as usize + 2 * (y >= 2) as usize;
+                let child = self.node(id).children[quadrant];
+                *cell = self.node(child).children[x % 2 + 2 * (y % 2)] == ALIVE;
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (i, (x, y)) in [(1, 1), (2, 1), (1, 2), (2, 2)].into_iter().enumerate() {
+            let neighborhood = cells[y - 1..=y + 1]
+                .iter()
+                .flat_map(|row| &row[x - 1..=x + 1])
+                .enumerate()
+                .fold(0, |res, (bit, cell)| res | (*cell as u16) << bit);
+            if self.rule.next_cell(neighborhood) {
+                next[i] = ALIVE;
+            }
+        }
+        self.join(next)
+    }
+
+    //returns the center of the node advanced by 2^step generations, step may be at most level - 2
+    fn successor(&mut self, id: NodeId, step: u8) -> NodeId {
+        if let Some(res) = self.results.get(&(id, step)) {
+            return *res;
+        }
+        let node = self.node(id);
+
+        let res = if node.population == 0 {
+            self.empty(node.level - 1)
+        } else if node.level == 2 {
+            self.step_leaves(id)
+        } else {
+            let [nw, ne, sw, se] = node.children.map(|c| self.node(c).children);
+
+            //the nine overlapping subnodes of half the size
+            let subnodes = [
+                self.join(nw),
+                self.join([nw[1], ne[0], nw[3], ne[2]]),
+                self.join(ne),
+                self.join([nw[2], nw[3], sw[0], sw[1]]),
+                self.join([nw[3], ne[2], sw[1], se[0]]),
+                self.join([ne[2], ne[3], se[0], se[1]]),
+                self.join(sw),
+                self.join([sw[1], se[0], sw[3], se[2]]),
+                self.join(se),
+            ];
+
+            //at full speed both halves of the recursion advance the pattern
+            let fullspeed = step == node.level - 2;
+            let mut r = [DEAD; 9];
+            for (r, subnode) in r.iter_mut().zip(subnodes) {
+                *r = if fullspeed {
+                    self.successor(subnode, step - 1)
+                } else {
+                    self.centre(subnode)
+                };
+            }
+            let step = if fullspeed { step - 1 } else { step };
+
+            let mut quadrants = [DEAD; 4];
+            for (quadrant, [a, b, c, d]) in quadrants.iter_mut().zip([
+                [r[0], r[1], r[3], r[4]],
+                [r[1], r[2], r[4], r[5]],
+                [r[3], r[4], r[6], r[7]],
+                [r[4], r[5], r[7], r[8]],
+            ]) {
+                let joined = self.join([a, b, c, d]);
+                *quadrant = self.successor(joined, step);
+            }
+            self.join(quadrants)
+        };
+
+        self.results.insert((id, step), res);
+        res
+    }
+
+    fn step(&mut self, step: u8) -> Result<(), StepError> {
+        //the root has to be large enough and the pattern must not be able to leave it
+        while self.node(self.root).level < step + 2 || !self.is_padded(self.root) {
+            if self.node(self.root).level + 1 >= MAXLEVEL {
+                return Err(StepError::OutOfRange);
+            }
+            self.root = self.expand(self.root);
+        }
+        if self.node(self.root).level >= MAXLEVEL {
+            return Err(StepError::OutOfRange);
+        }
+        let expanded = self.expand(self.root);
+        self.root = self.successor(expanded, step);
+        self.generation += 1 << step;
+
+        if self.nodes.len() > GCTHRESHOLD {
+            self.collect_garbage();
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self) -> Result<(), StepError> {
+        self.step(0)
+    }
+
+    //advances the pattern by 2^exponent generations, the pattern stays unchanged if that is not possible
+    pub fn jump(&mut self, exponent: u8) -> Result<(), StepError> {
+        if exponent >= MAXLEVEL - 3 {
+            return Err(StepError::ExponentTooLarge(exponent));
+        }
+        self.step(exponent)
+    }
+
+    fn copy_node(
+        &self,
+        id: NodeId,
+        target: &mut Self,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if id == DEAD || id == ALIVE {
+            return id;
+        }
+        if let Some(newid) = copied.get(&id) {
+            return *newid;
+        }
+        let children = self
+            .node(id)
+            .children
+            .map(|c| self.copy_node(c, target, copied));
+        let newid = target.join(children);
+        copied.insert(id, newid);
+        newid
+    }
+
+    //rebuilds the node storage with only the nodes reachable from the root
+    fn collect_garbage(&mut self) {
+        let mut target = Self::new();
+        target.rule = self.rule;
+        target.generation = self.generation;
+        target.root = self.copy_node(self.root, &mut target, &mut HashMap::new());
+        *self = target;
+    }
+
+    fn collect_alive(&self, id: NodeId, origin: (isize, isize), res: &mut Vec<(isize, isize)>) {
+        let node = self.node(id);
+        if node.population == 0 {
+            return;
+        }
+        if node.level == 0 {
+            res.push(origin);
+            return;
+        }
+        let half = 1 << (node.level - 1);
+        for (quadrant, child) in node.children.into_iter().enumerate() {
+            let offset = (
+                (quadrant % 2) as isize * half,
+                (quadrant / 2) as isize * half,
+            );
+            self.collect_alive(child, (origin.0 + offset.0, origin.1 + offset.1), res);
+        }
+    }
+
+    pub fn alive_cells(&self) -> Vec<(isize, isize)> {
+        let half = self.half_width();
+        let mut res = Vec::new();
+        self.collect_alive(self.root, (-half, -half), &mut res);
+        res
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.population() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = self.empty(3);
+        self.generation = 0;
+    }
+}
+
+impl Default for HashLife {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn r_pentomino(rule: &str) -> Field {
+        let mut field = Field::new(8);
+        field.set_rule(rule.parse().unwrap());
+        for coords in [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)] {
+            field.set_cell(coords, true);
+        }
+        field
+    }
+
+    fn alive(field: &Field) -> HashSet<(isize, isize)> {
+        field.alive_cells().collect()
+    }
+
+    #[test]
+    fn jump_matches_field_update() {
+        for rule in ["B3/S23", "B36/S23", "B2-a/S12", "B3/S2-i34q"] {
+            let mut field = r_pentomino(rule);
+            let mut hashlife = HashLife::from_field(&field);
+            for exponent in [0, 3, 5, 2, 6] {
+                hashlife.jump(exponent).unwrap();
+                for _ in 0..1 << exponent {
+                    field.update();
+                }
+                let mut result = Field::new(8);
+                hashlife.write_to_field(&mut result);
+                assert_eq!(
+                    alive(&result),
+                    alive(&field),
+                    "{rule} generation {}",
+                    field.generation()
+                );
+                assert_eq!(result.generation(), field.generation());
+                assert_eq!(hashlife.population(), field.population());
+            }
+        }
+    }
+
+    #[test]
+    fn update_matches_jump_by_one() {
+        let field = r_pentomino("B3/S23");
+        let mut stepped = HashLife::from_field(&field);
+        let mut jumped = HashLife::from_field(&field);
+        for _ in 0..16 {
+            stepped.update().unwrap();
+        }
+        jumped.jump(4).unwrap();
+        let (mut a, mut b) = (stepped.alive_cells(), jumped.alive_cells());
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn rejects_jumps_beyond_the_largest_node() {
+        let mut hashlife = HashLife::from_field(&r_pentomino("B3/S23"));
+        for exponent in [MAXLEVEL - 3, MAXLEVEL, u8::MAX] {
+            assert_eq!(
+                hashlife.jump(exponent),
+                Err(StepError::ExponentTooLarge(exponent))
+            );
+        }
+        assert_eq!(hashlife.generation(), 0);
+
+        //a glider at the edge of the coordinate range can't move on
+        let mut edge = HashLife::new();
+        let corner = (1 << (MAXLEVEL - 2)) - 4;
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            edge.set_cell((corner + x, corner + y), true);
+        }
+        assert_eq!(edge.jump(MAXLEVEL - 4), Err(StepError::OutOfRange));
+        assert_eq!(edge.population(), 5);
+    }
+}
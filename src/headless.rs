@@ -0,0 +1,365 @@
+use crate::field::Field;
+use crate::hashlife::HashLife;
+use crate::pattern::{self, macrocell, PatternError};
+use crate::rule::{ParseRuleError, Rule};
+use crate::topology::{ParseTopologyError, Topology};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const RUNCOMMAND: &str = "run";
+//the arguments after the name of the program
+pub const USAGE: &str =
+    "run <pattern> [--gens <n>] [--rule <rule>] [--topology <topology>] [--out <file>] [--stats]";
+const CHUNKSIZE: usize = 8;
+
+#[derive(Debug)]
+pub enum RunError {
+    MissingPattern,
+    MissingValue(String),
+    UnknownArgument(String),
+    InvalidGenerations(String),
+    InvalidRule(ParseRuleError),
+    InvalidTopology(ParseTopologyError),
+    Pattern(PathBuf, PatternError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::MissingPattern => write!(f, "No pattern file given"),
+            RunError::MissingValue(flag) => write!(f, "Missing value for {flag}"),
+            RunError::UnknownArgument(arg) => write!(f, "Unknown argument: {arg}"),
+            RunError::InvalidGenerations(value) => {
+                write!(f, "Invalid number of generations: {value}")
+            }
+            RunError::InvalidRule(err) => write!(f, "{err}"),
+            RunError::InvalidTopology(err) => write!(f, "{err}"),
+            RunError::Pattern(path, err) => write!(f, "{}: {err}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+impl RunError {
+    //wrong arguments are answered with the usage, failing files only with the error
+    pub fn is_usage(&self) -> bool {
+        !matches!(self, RunError::Pattern(..))
+    }
+}
+
+#[derive(Debug)]
+pub struct RunOptions {
+    pub pattern: PathBuf,
+    pub gens: u64,
+    pub rule: Option<Rule>,
+    pub topology: Option<Topology>,
+    pub out: Option<PathBuf>,
+    pub stats: bool,
+}
+
+impl RunOptions {
+    //the arguments after "run", flags take their value as next argument or after a '='
+    pub fn parse(args: impl Iterator<Item = OsString>) -> Result<Self, RunError> {
+        let mut pattern = None;
+        let mut gens = 0;
+        let mut rule = None;
+        let mut topology = None;
+        let mut out = None;
+        let mut stats = false;
+
+        let mut args = args.map(|arg| arg.to_string_lossy().into_owned());
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .map(str::to_string)
+                    .or_else(|| args.next())
+                    .ok_or_else(|| RunError::MissingValue(flag.clone()))
+            };
+            match flag.as_str() {
+                "--gens" => {
+                    let value = value()?;
+                    gens = value
+                        .parse()
+                        .map_err(|_| RunError::InvalidGenerations(value))?;
+                }
+                "--rule" => rule = Some(value()?.parse().map_err(RunError::InvalidRule)?),
+                "--topology" => {
+                    topology = Some(value()?.parse().map_err(RunError::InvalidTopology)?)
+                }
+                "--out" => out = Some(PathBuf::from(value()?)),
+                "--stats" if inline.is_none() => stats = true,
+                _ if pattern.is_none() && !arg.starts_with("--") => {
+                    pattern = Some(PathBuf::from(arg))
+                }
+                _ => return Err(RunError::UnknownArgument(arg)),
+            }
+        }
+
+        Ok(Self {
+            pattern: pattern.ok_or(RunError::MissingPattern)?,
+            gens,
+            rule,
+            topology,
+            out,
+            stats,
+        })
+    }
+}
+
+pub struct RunStats {
+    //the generations actually evolved, fewer than asked for if the pattern died out
+    pub gens: u64,
+    pub generation: u64,
+    pub diedout: bool,
+    pub population: u64,
+    pub boundingbox: Option<((isize, isize), (isize, isize))>,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.diedout {
+            writeln!(f, "Generation: {} (died out)", self.generation)?;
+        } else {
+            writeln!(f, "Generation: {}", self.generation)?;
+        }
+        writeln!(f, "Population: {}", self.population)?;
+        match self.boundingbox {
+            Some((min, max)) => writeln!(
+                f,
+                "Bounding box: ({}, {}) - ({}, {}), {}x{}",
+                min.0,
+                min.1,
+                max.0,
+                max.1,
+                max.0 - min.0 + 1,
+                max.1 - min.1 + 1
+            )?,
+            None => writeln!(f, "Bounding box: -")?,
+        }
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            write!(
+                f,
+                "Time: {secs:.3}s ({:.0} generations/s)",
+                self.gens as f64 / secs
+            )
+        } else {
+            write!(f, "Time: {secs:.3}s")
+        }
+    }
+}
+
+//same formats as opening and saving in the window
+fn read_field(path: &Path, field: &mut Field) -> Result<(), PatternError> {
+    if pattern::is_macrocell(path) {
+        macrocell::read_file(path)?.write_to_field(field);
+    } else {
+        pattern::read_file(path)?.load_into(field);
+    }
+    Ok(())
+}
+
+fn write_field(path: &Path, field: &Field) -> Result<(), PatternError> {
+    if pattern::is_macrocell(path) {
+        macrocell::write_file(path, &HashLife::from_field(field))
+    } else {
+        pattern::write_field(path, field)
+    }
+}
+
+//loads the pattern, evolves it without any window and saves the result, the time covers only the evolution
+pub fn run(options: &RunOptions, chunksize: usize) -> Result<RunStats, RunError> {
+    let mut field = Field::new(chunksize);
+    read_field(&options.pattern, &mut field)
+        .map_err(|err| RunError::Pattern(options.pattern.clone(), err))?;
+    if let Some(rule) = options.rule {
+        field.set_rule(rule);
+    }
+    if let Some(topology) = options.topology {
+        field.set_topology(topology);
+    }
+
+    let start = Instant::now();
+    let first = field.generation();
+    let end = first.saturating_add(options.gens);
+    let mut diedout = false;
+    while field.generation() < end {
+        //nothing will ever be born again, as B0 rules are not supported, dying cells still have to decay
+        if field.is_empty() {
+            diedout = true;
+            break;
+        }
+        field.update_threaded();
+    }
+    let elapsed = start.elapsed();
+
+    if let Some(out) = &options.out {
+        write_field(out, &field).map_err(|err| RunError::Pattern(out.clone(), err))?;
+    }
+
+    Ok(RunStats {
+        gens: field.generation() - first,
+        generation: field.generation(),
+        diedout,
+        population: field.population(),
+        boundingbox: field.bounding_box(),
+        elapsed,
+    })
+}
+
+//<program> run <pattern> ..., prints the stats or errors and returns the exit code
+pub fn run_command(program: &str, args: impl Iterator<Item = OsString>) -> i32 {
+    let options = match RunOptions::parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\nusage: {program} {USAGE}");
+            return 2;
+        }
+    };
+    match run(&options, CHUNKSIZE) {
+        Ok(stats) => {
+            if options.stats {
+                println!("{stats}");
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            if err.is_usage() {
+                eprintln!("usage: {program} {USAGE}");
+            }
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = OsString> {
+        args.iter()
+            .map(OsString::from)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    //a file in the temporary directory, removed again when the test is done
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("game-of-life-{}-{name}", std::process::id()));
+            fs::write(&path, content).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = RunOptions::parse(args(&[
+            "glider.rle",
+            "--gens",
+            "100",
+            "--rule=B36/S23",
+            "--topology",
+            "T10,10",
+            "--out=out.mc",
+            "--stats",
+        ]))
+        .unwrap();
+        assert_eq!(options.pattern, PathBuf::from("glider.rle"));
+        assert_eq!(options.gens, 100);
+        assert_eq!(options.rule, Some("B36/S23".parse().unwrap()));
+        assert_eq!(options.topology, Some(Topology::Torus(10, 10)));
+        assert_eq!(options.out, Some(PathBuf::from("out.mc")));
+        assert!(options.stats);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let err = |list: &[&str]| RunOptions::parse(args(list)).unwrap_err();
+        assert!(matches!(err(&[]), RunError::MissingPattern));
+        assert!(matches!(
+            err(&["a.rle", "--gens"]),
+            RunError::MissingValue(_)
+        ));
+        assert!(matches!(
+            err(&["a.rle", "--gens", "-1"]),
+            RunError::InvalidGenerations(_)
+        ));
+        assert!(matches!(
+            err(&["a.rle", "--rule", "B3/S9"]),
+            RunError::InvalidRule(_)
+        ));
+        assert!(matches!(
+            err(&["a.rle", "b.rle"]),
+            RunError::UnknownArgument(_)
+        ));
+        assert!(matches!(
+            err(&["a.rle", "--stats=yes"]),
+            RunError::UnknownArgument(_)
+        ));
+        assert!(err(&["a.rle", "--fast"]).is_usage());
+    }
+
+    #[test]
+    fn evolves_and_writes_the_pattern() {
+        let input = TempFile::new("blinker.rle", "#CXRLE Pos=0,0 Gen=4\nx = 3, y = 1\n3o!\n");
+        let output = TempFile::new("blinker-out.rle", "");
+        let options = RunOptions::parse(args(&[
+            input.0.to_str().unwrap(),
+            "--gens=5",
+            "--out",
+            output.0.to_str().unwrap(),
+        ]))
+        .unwrap();
+
+        let stats = run(&options, CHUNKSIZE).unwrap();
+        assert_eq!((stats.gens, stats.generation, stats.diedout), (5, 9, false));
+        assert_eq!(stats.population, 3);
+        assert_eq!(stats.boundingbox, Some(((1, -1), (1, 1))));
+
+        let written = pattern::read_file(&output.0).unwrap();
+        assert_eq!(written.generation, Some(9));
+        assert_eq!(written.origin, Some((1, -1)));
+        assert_eq!(written.size, (1, 3));
+    }
+
+    #[test]
+    fn stops_when_the_pattern_dies_out() {
+        //a single cell dies in the first generation, the dying states of the rule take three more
+        let input = TempFile::new("decay.rle", "x = 1, y = 1, rule = B3/S23/C5\no!\n");
+        let options =
+            RunOptions::parse(args(&[input.0.to_str().unwrap(), "--gens", "1000000"])).unwrap();
+
+        let stats = run(&options, CHUNKSIZE).unwrap();
+        assert_eq!((stats.gens, stats.generation, stats.diedout), (4, 4, true));
+        assert_eq!((stats.population, stats.boundingbox), (0, None));
+        assert!(stats.to_string().starts_with("Generation: 4 (died out)\n"));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let options = RunOptions::parse(args(&["/nonexistent/pattern.rle"])).unwrap();
+        let err = run(&options, CHUNKSIZE).err().unwrap();
+        assert!(matches!(err, RunError::Pattern(..)));
+        assert!(!err.is_usage());
+    }
+}
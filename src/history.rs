@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use crate::field::{Field, FieldState, Shape, State};
+
+//amount of actions that can be undone, older ones get dropped
+const HISTORYLIMIT: usize = 200;
+
+#[derive(Clone, Copy)]
+struct CellChange {
+    coords: (isize, isize),
+    old: State,
+    new: State,
+}
+
+enum Action {
+    //cells changed by the user, e.g. one drawn stroke or a placed shape
+    Edit(Vec<CellChange>),
+    //the whole field before a generation step or a clear
+    State(FieldState),
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Action>,
+    redo: Vec<Action>,
+    pending: Vec<CellChange>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, action: Action) {
+        if self.undo.len() == HISTORYLIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(action);
+        self.redo.clear();
+    }
+
+    //edits until end_edit are undone as one action
+    pub fn begin_edit(&mut self) {
+        self.end_edit();
+    }
+
+    pub fn end_edit(&mut self) {
+        if !self.pending.is_empty() {
+            let changes = std::mem::take(&mut self.pending);
+            self.push(Action::Edit(changes));
+        }
+    }
+
+    pub fn set_cell(&mut self, field: &mut Field, coords: (isize, isize), val: bool) {
+        self.set_state(field, coords, val as State);
+    }
+
+    pub fn set_state(&mut self, field: &mut Field, coords: (isize, isize), state: State) {
+        let old = field.get_state(coords);
+        if old != state {
+            field.set_state(coords, state);
+            self.pending.push(CellChange {
+                coords,
+                old,
+                new: state,
+            });
+        }
+    }
+
+    pub fn set_shape_at(&mut self, field: &mut Field, coords: (isize, isize), shape: &Shape) {
+        for (x, line) in shape.iter().enumerate() {
+            for (y, item) in line.iter().enumerate() {
+                if let Some(state) = item {
+                    self.set_state(
+                        field,
+                        (coords.0 + x as isize, coords.1 + y as isize),
+                        *state,
+                    );
+                }
+            }
+        }
+    }
+
+    //has to be called before the field gets changed by anything other than an edit
+    pub fn save_state(&mut self, field: &Field) {
+        self.end_edit();
+        self.push(Action::State(field.state()));
+    }
+
+    fn apply(field: &mut Field, action: Action, forward: bool) -> Action {
+        match action {
+            Action::Edit(changes) => {
+                if forward {
+                    for change in &changes {
+                        field.set_state(change.coords, change.new);
+                    }
+                } else {
+                    for change in changes.iter().rev() {
+                        field.set_state(change.coords, change.old);
+                    }
+                }
+                Action::Edit(changes)
+            }
+            Action::State(state) => {
+                let cur = field.state();
+                field.restore(state);
+                Action::State(cur)
+            }
+        }
+    }
+
+    pub fn undo(&mut self, field: &mut Field) -> bool {
+        self.end_edit();
+        match self.undo.pop_back() {
+            Some(action) => {
+                let action = Self::apply(field, action, false);
+                self.redo.push(action);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, field: &mut Field) -> bool {
+        self.end_edit();
+        match self.redo.pop() {
+            Some(action) => {
+                let action = Self::apply(field, action, true);
+                self.undo.push_back(action);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    fn cells(field: &Field) -> Vec<((isize, isize), State)> {
+        let mut cells = match field.bounding_box() {
+            Some((min, max)) => field.cells_in(min, max),
+            None => Vec::new(),
+        };
+        cells.sort_unstable();
+        cells
+    }
+
+    fn glider() -> Field {
+        let mut field = Field::new(8);
+        for coords in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            field.set_cell(coords, true);
+        }
+        field
+    }
+
+    #[test]
+    fn undoes_and_redoes_cell_edits() {
+        let mut field = glider();
+        field.set_rule("B3/S23/C4".parse().unwrap());
+        let before = cells(&field);
+        let mut history = History::new();
+
+        history.begin_edit();
+        history.set_cell(&mut field, (1, 0), false);
+        history.set_cell(&mut field, (-5, 9), true);
+        history.set_state(&mut field, (2, 1), 3);
+        //setting a cell twice is undone to its first state
+        history.set_cell(&mut field, (-5, 9), false);
+        history.set_cell(&mut field, (-5, 9), true);
+        history.end_edit();
+        let after = cells(&field);
+        assert_eq!(
+            after,
+            [
+                ((-5, 9), 1),
+                ((0, 2), 1),
+                ((1, 2), 1),
+                ((2, 1), 3),
+                ((2, 2), 1)
+            ]
+        );
+
+        assert!(history.undo(&mut field));
+        assert_eq!(cells(&field), before);
+        assert!(!history.undo(&mut field));
+        assert!(history.redo(&mut field));
+        assert_eq!(cells(&field), after);
+        assert!(!history.redo(&mut field));
+        assert_eq!(field.population(), 4);
+    }
+
+    #[test]
+    fn pending_edits_and_new_actions() {
+        let mut field = Field::new(8);
+        let mut history = History::new();
+
+        //undo finishes an edit that is still going on
+        history.begin_edit();
+        history.set_cell(&mut field, (0, 0), true);
+        assert!(history.undo(&mut field));
+        assert!(field.is_empty());
+
+        //an edit without changes is not recorded, a new one drops what could be redone
+        history.begin_edit();
+        history.set_cell(&mut field, (3, 3), false);
+        history.end_edit();
+        assert!(history.redo(&mut field));
+        assert!(history.undo(&mut field));
+        history.begin_edit();
+        history.set_cell(&mut field, (1, 1), true);
+        history.end_edit();
+        assert!(!history.redo(&mut field));
+        assert_eq!(cells(&field), [((1, 1), 1)]);
+    }
+
+    #[test]
+    fn a_run_is_one_entry() {
+        let mut field = glider();
+        let mut history = History::new();
+        let before = cells(&field);
+
+        history.save_state(&field);
+        for _ in 0..12 {
+            field.update();
+        }
+        let after = cells(&field);
+        assert_eq!(field.generation(), 12);
+
+        assert!(history.undo(&mut field));
+        assert_eq!((cells(&field), field.generation()), (before.clone(), 0));
+        assert!(!history.undo(&mut field));
+        assert!(history.redo(&mut field));
+        assert_eq!((cells(&field), field.generation()), (after, 12));
+
+        //a rule change is part of the saved state
+        history.save_state(&field);
+        field.set_rule("B36/S23".parse().unwrap());
+        field.update();
+        assert!(history.undo(&mut field));
+        assert_eq!(field.rule(), Rule::conway());
+        assert_eq!(field.generation(), 12);
+    }
+
+    #[test]
+    fn drops_the_oldest_actions() {
+        let mut field = Field::new(8);
+        let mut history = History::new();
+        let extra = 5;
+
+        for x in 0..(HISTORYLIMIT + extra) as isize {
+            history.begin_edit();
+            history.set_cell(&mut field, (x, 0), true);
+            history.end_edit();
+        }
+        for _ in 0..HISTORYLIMIT {
+            assert!(history.undo(&mut field));
+        }
+        assert!(!history.undo(&mut field));
+        //the first edits can't be undone anymore
+        let remaining: Vec<_> = (0..extra as isize).map(|x| ((x, 0), 1)).collect();
+        assert_eq!(cells(&field), remaining);
+
+        for _ in 0..HISTORYLIMIT {
+            assert!(history.redo(&mut field));
+        }
+        assert_eq!(field.population(), (HISTORYLIMIT + extra) as u64);
+    }
+}
@@ -0,0 +1,12 @@
+//the simulation engine and pattern files, usable without the gui and its fltk dependency
+pub mod config;
+pub mod field;
+pub mod hashlife;
+pub mod headless;
+pub mod history;
+pub mod pattern;
+pub mod pool;
+pub mod rule;
+pub mod shapes;
+pub mod topology;
+pub mod transform;
@@ -4,24 +4,31 @@ use fltk::{
     app,
     app::remove_timeout3,
     button::{Button, CheckButton, ToggleButton},
-    enums::{FrameType, Shortcut},
-    input::FloatInput,
-    menu::{Choice, MenuFlag},
+    dialog::{alert_default, NativeFileChooser, NativeFileChooserType},
+    enums::{Align, CallbackTrigger, Color, Event, EventState, FrameType, Key, Shortcut},
+    input::{FloatInput, Input, IntInput},
+    menu::{Choice, MenuButton, MenuFlag},
     prelude::WidgetExt,
     prelude::*,
     text::TextDisplay,
     window::Window,
 };
 use std::cell::RefCell;
-use std::fs;
+use std::env;
 use std::path::PathBuf;
+use std::process;
 use std::rc::Rc;
 
 pub mod canvas;
-pub mod field;
 use crate::canvas::Canvas;
-use crate::field::Shape;
-
+use game_of_life::config::Config;
+use game_of_life::headless;
+use game_of_life::rule::Rule;
+use game_of_life::shapes::{self, ShapeLibrary};
+use game_of_life::topology::Topology;
+use game_of_life::transform::{self, Transform};
+
+const PROGRAM: &str = "game-of-life";
 const WIDTH: i32 = 800 * 2;
 const HEIGHT: i32 = 600 * 2;
 const TICKTIME: f64 = 0.05;
@@ -30,66 +37,83 @@ const INITIALUPDATEINTERVALL: f64 = 0.1;
 const XSTARTOFFSET: i32 = 0;
 const YSTARTOFFSET: i32 = 0;
 const CHUNKSIZE: usize = 8;
-
-//helper function for rotating double vecs
-fn mirror_diag<T: Copy>(vec: &mut Vec<Vec<Option<T>>>) {
-    let mut rotshape = Vec::new();
-    let vect_maxlen = vec.iter().fold(0, |x, y| std::cmp::max(x, y.len()));
-
-    for i in 0..vect_maxlen {
-        let mut curcolumn = Vec::new();
-        let shapeiter = vec.iter();
-        for j in shapeiter {
-            curcolumn.push(*j.get(i).unwrap_or(&None));
-        }
-        rotshape.push(curcolumn);
+const INITIALJUMPEXPONENT: u8 = 10;
+const MAXJUMPEXPONENT: u8 = 24;
+const INITIALDENSITY: f64 = 0.5;
+const PATTERNFILTER: &str = "Pattern files\t*.{rle,cells,lif,life,mc}";
+const SHAPEPOLLTIME: f64 = 1.0;
+const SESSIONFILTER: &str = "Session files\t*.session";
+
+//asks for a file to open or save, None if the dialog got cancelled
+fn choose_file(dialogtype: NativeFileChooserType, filter: &str) -> Option<PathBuf> {
+    let mut chooser = NativeFileChooser::new(dialogtype);
+    chooser.set_filter(filter);
+    chooser.show();
+    let path = chooser.filename();
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
     }
-    *vec = rotshape;
 }
 
-//helper function turning an iterator of results into a result that either is the iterator over the non-error type or the first encounterd error
-fn return_first_err<T, E>(
-    vec: impl Iterator<Item = Result<T, E>>,
-) -> Result<impl Iterator<Item = T>, E> {
-    let mut res = Vec::new();
+//rebuilds the shape menu, the selected shape stays selected if it still exists
+fn fill_shape_menu(
+    menu: &mut Choice,
+    library: &ShapeLibrary,
+    canvas: &Rc<RefCell<Canvas>>,
+    shapebuttons: &[Rc<RefCell<Button>>],
+    selectedshape: &Rc<RefCell<Option<Vec<String>>>>,
+) {
+    menu.clear();
+    {
+        let canvas = canvas.clone();
+        let shapebuttons = shapebuttons.to_vec();
+        let selectedshape = selectedshape.clone();
+
+        menu.add("None", Shortcut::None, MenuFlag::Normal, move |_| {
+            canvas.borrow_mut().set_curshape(None);
+            *selectedshape.borrow_mut() = None;
+            for button in &shapebuttons {
+                button.borrow_mut().deactivate();
+            }
+        });
+    }
 
-    for x in vec.into_iter() {
-        match x {
-            Ok(x) => res.push(x),
-            Err(err) => return Err(err),
-        }
+    for file in library.shapes() {
+        let canvas = canvas.clone();
+        let shapebuttons = shapebuttons.to_vec();
+        let selectedshape = selectedshape.clone();
+        let shape = file.shape.clone();
+        let menupath = file.menupath.clone();
+
+        menu.add(
+            shapes::menu_label(&file.menupath).as_str(),
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                canvas.borrow_mut().set_curshape(Some(shape.clone()));
+                *selectedshape.borrow_mut() = Some(menupath.clone());
+                for button in &shapebuttons {
+                    button.borrow_mut().activate();
+                }
+            },
+        );
     }
 
-    Ok(res.into_iter())
+    let index = match &*selectedshape.borrow() {
+        Some(menupath) => menu.find_index(&shapes::menu_label(menupath)).max(0),
+        None => 0,
+    };
+    menu.set_value(index);
 }
 
-fn parse_file(file: &PathBuf) -> Option<Shape> {
-    //Reads entire file into buffer, not a great idea for huge files
-    let bytebuf: Vec<u8> = std::fs::read(file)
-        .unwrap_or_else(|_| panic!("file read error at {}", file.to_str().unwrap_or("")));
-    let mut curshape = Vec::new();
-
-    let bytebuflines = bytebuf
-        .split(|b| *b == b'\n')
-        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
-
-    for line in bytebuflines {
-        let mut curline = Vec::new();
-        for b in line {
-            if *b == b'0' {
-                curline.push(Some(false));
-            } else if *b == b'1' {
-                curline.push(Some(true));
-            } else {
-                curline.push(None);
-            }
-        }
-        curshape.push(curline);
+fn main() {
+    let mut args = env::args_os().skip(1);
+    if args.next().is_some_and(|arg| arg == headless::RUNCOMMAND) {
+        process::exit(headless::run_command(PROGRAM, args));
     }
-    Some(curshape)
-}
 
-fn main() {
     let app = app::App::default().with_scheme(app::Scheme::Gtk);
 
     let mut wind = Window::default()
@@ -110,6 +134,11 @@ fn main() {
     let btn_step = Button::default().with_label("Step");
     wind.add(&btn_step);
 
+    let mut btn_back = Button::default().with_label("Back");
+    btn_back.set_shortcut(Shortcut::Ctrl | 'z');
+    btn_back.set_tooltip("Undo the last edit or generation (Ctrl+Z), redo with Ctrl+Y");
+    wind.add(&btn_back);
+
     let btn_clear = Button::default().with_label("Clear");
     wind.add(&btn_clear);
 
@@ -121,6 +150,10 @@ fn main() {
     let btn_drawchunks = CheckButton::default().with_label("Draw chunks");
     wind.add(&btn_drawchunks);
 
+    let mut btn_fit = Button::default().with_label("Zoom to fit");
+    btn_fit.set_tooltip("Show the whole pattern, the mouse wheel zooms in and out");
+    wind.add(&btn_fit);
+
     let mnu_shapeselect = Choice::default().with_label("Insert shape:");
     wind.add(&mnu_shapeselect);
 
@@ -137,31 +170,131 @@ fn main() {
     inp_update_intervall.set_value(format!("{}", INITIALUPDATEINTERVALL).as_str());
     wind.add(&inp_update_intervall);
 
+    let mut inp_rule = Input::default().with_label("Rule:");
+    inp_rule.set_value(format!("{}", Rule::default()).as_str());
+    inp_rule.set_trigger(CallbackTrigger::Changed);
+    wind.add(&inp_rule);
+
+    let mut inp_topology = Input::default().with_label("Topology:");
+    inp_topology.set_value(format!("{}", Topology::default()).as_str());
+    inp_topology.set_trigger(CallbackTrigger::EnterKeyAlways);
+    inp_topology.set_tooltip(
+        "Applied with Enter: Plane, T<w>,<h> (torus), P<w>,<h> (bounded plane), K<w>,<h> (klein bottle) or C<w>,<h> (cross-surface)",
+    );
+    wind.add(&inp_topology);
+
+    let mut inp_jump_exponent = IntInput::default().with_label("Jump 2^");
+    inp_jump_exponent.set_value(format!("{}", INITIALJUMPEXPONENT).as_str());
+    wind.add(&inp_jump_exponent);
+
+    let btn_jump = Button::default().with_label("Jump");
+    wind.add(&btn_jump);
+
+    let btn_open = Button::default().with_label("Open");
+    wind.add(&btn_open);
+
+    let btn_save = Button::default().with_label("Save");
+    wind.add(&btn_save);
+
+    let btn_open_session = Button::default().with_label("Open session");
+    wind.add(&btn_open_session);
+
+    let btn_save_session = Button::default().with_label("Save session");
+    wind.add(&btn_save_session);
+
+    let mut btn_copy = Button::default().with_label("Copy");
+    btn_copy.set_shortcut(Shortcut::Ctrl | 'c');
+    btn_copy
+        .set_tooltip("Copy the selection (Ctrl+C), select with Shift and the right mouse button");
+    wind.add(&btn_copy);
+
+    let mut btn_cut = Button::default().with_label("Cut");
+    btn_cut.set_shortcut(Shortcut::Ctrl | 'x');
+    wind.add(&btn_cut);
+
+    let mut btn_paste = Button::default().with_label("Paste");
+    btn_paste.set_shortcut(Shortcut::Ctrl | 'v');
+    btn_paste.set_tooltip("Place the copied cells with the right mouse button (Ctrl+V)");
+    wind.add(&btn_paste);
+
+    let mut btn_delete = Button::default().with_label("Delete");
+    btn_delete.set_shortcut(Shortcut::None | Key::Delete);
+    wind.add(&btn_delete);
+
+    let mut inp_density = FloatInput::default().with_label("Density");
+    inp_density.set_value(format!("{}", INITIALDENSITY).as_str());
+    wind.add(&inp_density);
+
+    let mut btn_random = Button::default().with_label("Random");
+    btn_random.set_tooltip("Fill the selection with random cells of the given density");
+    wind.add(&btn_random);
+
+    let mut mnu_transform = MenuButton::default().with_label("Transform");
+    mnu_transform.set_tooltip(
+        "Transform the selection or the current shape, Ctrl and the arrow keys move them",
+    );
+    wind.add(&mnu_transform);
+
     let mut lbl_coords = TextDisplay::default();
     lbl_coords.set_frame(FrameType::NoBox); //<- i hate this
+    lbl_coords.set_align(Align::Top | Align::Left);
     wind.add(&lbl_coords);
 
     let canvas = Rc::new(RefCell::new(canvas));
     let btn_stop_toggle = Rc::new(RefCell::new(btn_stop_toggle));
     let btn_drawchunks = Rc::new(RefCell::new(btn_drawchunks));
+    let btn_fit = Rc::new(RefCell::new(btn_fit));
     let mnu_shapeselect = Rc::new(RefCell::new(mnu_shapeselect));
     let btn_step = Rc::new(RefCell::new(btn_step));
+    let btn_back = Rc::new(RefCell::new(btn_back));
     let btn_clear = Rc::new(RefCell::new(btn_clear));
     let btn_mirror_shape = Rc::new(RefCell::new(btn_mirror_shape));
     let btn_rotate_shape: Rc<RefCell<Button>> = Rc::new(RefCell::new(btn_rotate_shape));
     let inp_update_intervall = Rc::new(RefCell::new(inp_update_intervall));
+    let inp_rule = Rc::new(RefCell::new(inp_rule));
+    let inp_topology = Rc::new(RefCell::new(inp_topology));
+    let inp_jump_exponent = Rc::new(RefCell::new(inp_jump_exponent));
+    let btn_jump = Rc::new(RefCell::new(btn_jump));
+    let btn_open = Rc::new(RefCell::new(btn_open));
+    let btn_save = Rc::new(RefCell::new(btn_save));
+    let btn_open_session = Rc::new(RefCell::new(btn_open_session));
+    let btn_save_session = Rc::new(RefCell::new(btn_save_session));
+    let btn_copy = Rc::new(RefCell::new(btn_copy));
+    let btn_cut = Rc::new(RefCell::new(btn_cut));
+    let btn_paste = Rc::new(RefCell::new(btn_paste));
+    let btn_delete = Rc::new(RefCell::new(btn_delete));
+    let inp_density = Rc::new(RefCell::new(inp_density));
+    let btn_random = Rc::new(RefCell::new(btn_random));
+    let mnu_transform = Rc::new(RefCell::new(mnu_transform));
     let lbl_coords = Rc::new(RefCell::new(lbl_coords));
 
     {
         let canvas = canvas.clone();
         let btn_stop_toggle = btn_stop_toggle.clone();
         let btn_drawchunks = btn_drawchunks.clone();
+        let btn_fit = btn_fit.clone();
         let mnu_shapeselect = mnu_shapeselect.clone();
         let btn_step = btn_step.clone();
+        let btn_back = btn_back.clone();
         let btn_clear = btn_clear.clone();
         let btn_mirror_shape = btn_mirror_shape.clone();
         let btn_rotate_shape = btn_rotate_shape.clone();
         let inp_update_intervall = inp_update_intervall.clone();
+        let inp_rule = inp_rule.clone();
+        let inp_topology = inp_topology.clone();
+        let inp_jump_exponent = inp_jump_exponent.clone();
+        let btn_jump = btn_jump.clone();
+        let btn_open = btn_open.clone();
+        let btn_save = btn_save.clone();
+        let btn_open_session = btn_open_session.clone();
+        let btn_save_session = btn_save_session.clone();
+        let btn_copy = btn_copy.clone();
+        let btn_cut = btn_cut.clone();
+        let btn_paste = btn_paste.clone();
+        let btn_delete = btn_delete.clone();
+        let inp_density = inp_density.clone();
+        let btn_random = btn_random.clone();
+        let mnu_transform = mnu_transform.clone();
         let lbl_coords = lbl_coords.clone();
 
         wind.resize_callback(move |_, _, _, width, height| {
@@ -169,7 +302,7 @@ fn main() {
             canvas.borrow_mut().set_size(width, height);
 
             let padding = 5;
-            let mut cur_x = width - padding - 100 - padding - 40 - padding - 40;
+            let mut cur_x = width - padding - 100 - padding - 40 - padding - 40 - padding - 40;
             let mut cur_y = padding;
 
             btn_clear.borrow_mut().set_pos(cur_x, cur_y);
@@ -177,6 +310,11 @@ fn main() {
 
             cur_x += padding + 40;
 
+            btn_back.borrow_mut().set_pos(cur_x, cur_y);
+            btn_back.borrow_mut().set_size(40, 40);
+
+            cur_x += padding + 40;
+
             btn_step.borrow_mut().set_pos(cur_x, cur_y);
             btn_step.borrow_mut().set_size(40, 40);
 
@@ -190,6 +328,12 @@ fn main() {
             btn_drawchunks.borrow_mut().set_pos(cur_x, cur_y);
             btn_drawchunks.borrow_mut().set_size(100, 20);
 
+            //below clear, back and step
+            btn_fit
+                .borrow_mut()
+                .set_pos(cur_x - 3 * (padding + 40), cur_y);
+            btn_fit.borrow_mut().set_size(3 * 40 + 2 * padding, 20);
+
             cur_y += padding + 20;
 
             mnu_shapeselect.borrow_mut().set_pos(cur_x, cur_y);
@@ -211,117 +355,443 @@ fn main() {
             inp_update_intervall.borrow_mut().set_pos(cur_x, cur_y);
             inp_update_intervall.borrow_mut().set_size(100, 20);
 
+            cur_y += padding + 20;
+
+            inp_rule.borrow_mut().set_pos(cur_x, cur_y);
+            inp_rule.borrow_mut().set_size(100, 20);
+
+            cur_y += padding + 20;
+
+            inp_topology.borrow_mut().set_pos(cur_x, cur_y);
+            inp_topology.borrow_mut().set_size(100, 20);
+
+            cur_y += padding + 20;
+
+            inp_jump_exponent.borrow_mut().set_pos(cur_x, cur_y);
+            inp_jump_exponent.borrow_mut().set_size(45, 20);
+
+            cur_x += 2 * padding + 45;
+
+            btn_jump.borrow_mut().set_pos(cur_x, cur_y);
+            btn_jump.borrow_mut().set_size(45, 20);
+
+            cur_x -= 2 * padding + 45;
+            cur_y += padding + 20;
+
+            btn_open.borrow_mut().set_pos(cur_x, cur_y);
+            btn_open.borrow_mut().set_size(45, 20);
+
+            cur_x += 2 * padding + 45;
+
+            btn_save.borrow_mut().set_pos(cur_x, cur_y);
+            btn_save.borrow_mut().set_size(45, 20);
+
+            cur_x -= 2 * padding + 45;
+            cur_y += padding + 20;
+
+            btn_open_session.borrow_mut().set_pos(cur_x, cur_y);
+            btn_open_session.borrow_mut().set_size(100, 20);
+
+            cur_y += padding + 20;
+
+            btn_save_session.borrow_mut().set_pos(cur_x, cur_y);
+            btn_save_session.borrow_mut().set_size(100, 20);
+
+            cur_y += padding + 20;
+
+            btn_copy.borrow_mut().set_pos(cur_x, cur_y);
+            btn_copy.borrow_mut().set_size(45, 20);
+
+            cur_x += 2 * padding + 45;
+
+            btn_cut.borrow_mut().set_pos(cur_x, cur_y);
+            btn_cut.borrow_mut().set_size(45, 20);
+
+            cur_x -= 2 * padding + 45;
+            cur_y += padding + 20;
+
+            btn_paste.borrow_mut().set_pos(cur_x, cur_y);
+            btn_paste.borrow_mut().set_size(45, 20);
+
+            cur_x += 2 * padding + 45;
+
+            btn_delete.borrow_mut().set_pos(cur_x, cur_y);
+            btn_delete.borrow_mut().set_size(45, 20);
+
+            cur_x -= 2 * padding + 45;
+            cur_y += padding + 20;
+
+            inp_density.borrow_mut().set_pos(cur_x, cur_y);
+            inp_density.borrow_mut().set_size(45, 20);
+
+            cur_x += 2 * padding + 45;
+
+            btn_random.borrow_mut().set_pos(cur_x, cur_y);
+            btn_random.borrow_mut().set_size(45, 20);
+
+            cur_x -= 2 * padding + 45;
+            cur_y += padding + 20;
+
+            mnu_transform.borrow_mut().set_pos(cur_x, cur_y);
+            mnu_transform.borrow_mut().set_size(100, 20);
+
             lbl_coords.borrow_mut().set_pos(0, height);
-            lbl_coords.borrow_mut().set_size(100, 0);
+            lbl_coords.borrow_mut().set_size(width, 0);
         });
     }
 
+    //shapes that fail to load are left out of the menu, the user gets told once the window is up
+    let (config, configerrors) = Config::load();
+    let (library, shapeerrors) = ShapeLibrary::load(config.shapedirs);
+    let library = Rc::new(RefCell::new(library));
+    let selectedshape: Rc<RefCell<Option<Vec<String>>>> = Rc::new(RefCell::new(None));
+    let shapebuttons = vec![btn_mirror_shape.clone(), btn_rotate_shape.clone()];
+
+    fill_shape_menu(
+        &mut mnu_shapeselect.borrow_mut(),
+        &library.borrow(),
+        &canvas,
+        &shapebuttons,
+        &selectedshape,
+    );
+
     {
         let canvas = canvas.clone();
-        let btn_mirror_shape = btn_mirror_shape.clone();
-        let btn_rotate_shape = btn_rotate_shape.clone();
 
-        mnu_shapeselect
-            .borrow_mut()
-            .add("None", Shortcut::None, MenuFlag::Normal, move |_| {
-                canvas.borrow_mut().set_curshape(None);
-                btn_mirror_shape.borrow_mut().deactivate();
-                btn_rotate_shape.borrow_mut().deactivate();
-            });
-        mnu_shapeselect.borrow_mut().set_value(0);
+        btn_clear.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().clear();
+        });
     }
 
     {
-        let mut shapedir = fs::read_dir("./shapes/");
-
-        match &mut shapedir {
-            Ok(shapedir) => {
-                let shapedir = return_first_err(shapedir);
-                match shapedir {
-                    Ok(shapedir) => {
-                        for x in shapedir {
-                            //TODO better error handling
-                            if x.metadata().unwrap().is_file() {
-                                let btn_mirror_shape = btn_mirror_shape.clone();
-                                let btn_rotate_shape = btn_rotate_shape.clone();
-                                let canvas = canvas.clone();
-
-                                mnu_shapeselect.borrow_mut().add(
-                                    x.file_name().into_string().unwrap().as_str(), //TODO better error handling
-                                    Shortcut::None,
-                                    MenuFlag::Normal,
-                                    move |_| {
-                                        let filepath = x.path();
-                                        let mut curshape = parse_file(&filepath);
-                                        if let Some(shape) = &mut curshape {
-                                            //we have to mirror along the (0,0) -- (1,1) diagonal due to how we read the file
-                                            mirror_diag(shape);
-                                        }
-                                        canvas.borrow_mut().set_curshape(curshape);
-                                        btn_mirror_shape.borrow_mut().activate();
-                                        btn_rotate_shape.borrow_mut().activate();
-                                    },
-                                );
-                            }
-                        }
-                    }
-                    Err(error) => println!("{error}"),
+        let canvas = canvas.clone();
+
+        btn_step.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().update_threaded();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        btn_back.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().undo();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        wind.handle(move |_, ev| {
+            //undo has its shortcut on the back button, redo has no button of its own
+            if ev == Event::Shortcut
+                && app::event_state().contains(EventState::Ctrl)
+                && app::event_key() == Key::from_char('y')
+                && canvas.borrow().drawmode()
+            {
+                canvas.borrow_mut().redo();
+                true
+            } else if ev == Event::Shortcut
+                && app::event_state().contains(EventState::Ctrl)
+                && canvas.borrow().drawmode()
+                && [Key::Left, Key::Right, Key::Up, Key::Down].contains(&app::event_key())
+            {
+                let (dx, dy) = match app::event_key() {
+                    Key::Left => (-1, 0),
+                    Key::Right => (1, 0),
+                    Key::Up => (0, -1),
+                    _ => (0, 1),
+                };
+                let mut canvas = canvas.borrow_mut();
+                if !canvas.move_selection(dx, dy) {
+                    canvas.move_curshape(dx, dy);
                 }
+                true
+            } else if ev == Event::Shortcut
+                && app::event_key() == Key::Escape
+                && canvas.borrow().selection().is_some()
+            {
+                //escape would close the window otherwise
+                canvas.borrow_mut().set_selection(None);
+                true
+            } else {
+                false
             }
-            Err(error) => println!("{error}"),
-        }
+        });
     }
 
     {
         let canvas = canvas.clone();
 
-        btn_clear.borrow_mut().set_callback(move |_| {
-            canvas.borrow_mut().clear();
+        btn_fit.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().zoom_to_fit();
         });
     }
 
     {
         let canvas = canvas.clone();
 
-        btn_step.borrow_mut().set_callback(move |_| {
-            canvas.borrow_mut().update_threaded(10, 50);
+        btn_copy.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().copy_selection();
         });
     }
 
     {
         let canvas = canvas.clone();
-        let curshape = canvas.borrow().get_curshaperef();
 
-        btn_mirror_shape.borrow_mut().set_callback(move |_| {
-            let mut curshape = curshape.borrow_mut();
-            if let Some(shape) = &mut *curshape {
-                shape.reverse();
+        btn_cut.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().cut_selection();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+        let mnu_shapeselect = mnu_shapeselect.clone();
+        let shapebuttons = shapebuttons.clone();
+        let selectedshape = selectedshape.clone();
+
+        btn_paste.borrow_mut().set_callback(move |_| {
+            if canvas.borrow_mut().paste() {
+                //the pasted cells are not one of the menu entries
+                mnu_shapeselect.borrow_mut().set_value(0);
+                *selectedshape.borrow_mut() = None;
+                for button in &shapebuttons {
+                    button.borrow_mut().activate();
+                }
             }
         });
     }
 
     {
-        let canvas: Rc<RefCell<Canvas>> = canvas.clone();
+        let canvas = canvas.clone();
+
+        btn_delete.borrow_mut().set_callback(move |_| {
+            canvas.borrow_mut().delete_selection();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+        let inp_density = inp_density.clone();
+
+        btn_random.borrow_mut().set_callback(move |_| {
+            let density = inp_density
+                .borrow()
+                .value()
+                .parse()
+                .unwrap_or(INITIALDENSITY)
+                .clamp(0.0, 1.0);
+            inp_density
+                .borrow_mut()
+                .set_value(format!("{density}").as_str());
+            canvas.borrow_mut().fill_selection_random(density);
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        inp_rule.borrow_mut().set_callback(move |handle| {
+            //only valid rules get applied, invalid ones are marked until they are corrected
+            match handle.value().parse::<Rule>() {
+                Ok(rule) => {
+                    canvas.borrow_mut().set_rule(rule);
+                    handle.set_text_color(Color::Black);
+                }
+                Err(_) => handle.set_text_color(Color::Red),
+            }
+            handle.redraw();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        inp_topology.borrow_mut().set_callback(move |handle| {
+            match handle.value().parse::<Topology>() {
+                Ok(topology) => {
+                    canvas.borrow_mut().set_topology(topology);
+                    handle.set_text_color(Color::Black);
+                }
+                Err(_) => handle.set_text_color(Color::Red),
+            }
+            handle.redraw();
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+        let inp_jump_exponent = inp_jump_exponent.clone();
+
+        btn_jump.borrow_mut().set_callback(move |_| {
+            let exponent = inp_jump_exponent
+                .borrow()
+                .value()
+                .parse()
+                .unwrap_or(INITIALJUMPEXPONENT)
+                .min(MAXJUMPEXPONENT);
+            //the alert runs the event loop, so the canvas must not be borrowed anymore
+            let res = canvas.borrow_mut().jump(exponent);
+            match res {
+                Ok(exponent) => inp_jump_exponent
+                    .borrow_mut()
+                    .set_value(format!("{exponent}").as_str()),
+                Err(err) => alert_default(&format!("Could not jump: {err}")),
+            }
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        btn_open.borrow_mut().set_callback(move |_| {
+            if let Some(path) = choose_file(NativeFileChooserType::BrowseFile, PATTERNFILTER) {
+                //the alert runs the event loop, so the canvas must not be borrowed anymore
+                let res = canvas.borrow_mut().open_file(&path);
+                if let Err(err) = res {
+                    alert_default(&format!("Could not open {}: {err}", path.display()));
+                }
+            }
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        btn_save.borrow_mut().set_callback(move |_| {
+            if let Some(mut path) =
+                choose_file(NativeFileChooserType::BrowseSaveFile, PATTERNFILTER)
+            {
+                if path.extension().is_none() {
+                    path.set_extension("rle");
+                }
+                let res = canvas.borrow().save_file(&path);
+                if let Err(err) = res {
+                    alert_default(&format!("Could not save {}: {err}", path.display()));
+                }
+            }
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        btn_mirror_shape.borrow_mut().set_callback(move |_| {
+            canvas
+                .borrow_mut()
+                .map_curshape(|shape| Transform::FlipHorizontal.apply(shape));
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
 
         btn_rotate_shape.borrow_mut().set_callback(move |_| {
-            let curshape = canvas.borrow().get_curshaperef();
-            let mut curshape = curshape.borrow_mut();
+            canvas
+                .borrow_mut()
+                .map_curshape(|shape| Transform::Rotate90.apply(shape));
+        });
+    }
 
-            if let Some(shape) = &mut *curshape {
-                let mut rotshape = Vec::new();
+    //the transformations work on the selection if there is one and on the current shape otherwise
+    for transform in &Transform::ALL[1..] {
+        let canvas = canvas.clone();
+        let transform = *transform;
+
+        mnu_transform.borrow_mut().add(
+            transform.name(),
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let mut canvas = canvas.borrow_mut();
+                if !canvas.transform_selection(transform) {
+                    canvas.map_curshape(|shape| transform.apply(shape));
+                }
+            },
+        );
+    }
 
-                let vect_maxlen = shape.iter().fold(0, |x, y| std::cmp::max(x, y.len()));
+    {
+        let canvas = canvas.clone();
 
-                for i in 0..vect_maxlen {
-                    let mut curcolumn = Vec::new();
-                    let shapeiter = shape.iter();
-                    for j in shapeiter {
-                        curcolumn.push(*j.get(i).unwrap_or(&None));
+        mnu_transform
+            .borrow_mut()
+            .add("Invert", Shortcut::None, MenuFlag::Normal, move |_| {
+                let mut canvas = canvas.borrow_mut();
+                if !canvas.invert_selection() {
+                    canvas.map_curshape(transform::invert);
+                }
+            });
+    }
+
+    {
+        let canvas = canvas.clone();
+
+        mnu_transform
+            .borrow_mut()
+            .add("Trim", Shortcut::None, MenuFlag::Normal, move |_| {
+                let mut canvas = canvas.borrow_mut();
+                if !canvas.trim_selection() {
+                    canvas.map_curshape(|shape| transform::trim(shape).0);
+                }
+            });
+    }
+
+    {
+        let canvas = canvas.clone();
+        let inp_update_intervall = inp_update_intervall.clone();
+        let mnu_shapeselect = mnu_shapeselect.clone();
+        let btn_mirror_shape = btn_mirror_shape.clone();
+        let btn_rotate_shape = btn_rotate_shape.clone();
+        let selectedshape = selectedshape.clone();
+
+        btn_open_session.borrow_mut().set_callback(move |_| {
+            if let Some(path) = choose_file(NativeFileChooserType::BrowseFile, SESSIONFILTER) {
+                let res = canvas.borrow_mut().open_session(&path);
+                match res {
+                    Ok(intervall) => {
+                        if let Some(intervall) = intervall {
+                            inp_update_intervall
+                                .borrow_mut()
+                                .set_value(format!("{intervall}").as_str());
+                        }
+                        //the shape of the session is not one of the menu entries anymore
+                        mnu_shapeselect.borrow_mut().set_value(0);
+                        *selectedshape.borrow_mut() = None;
+                        if canvas.borrow().get_curshaperef().borrow().is_some() {
+                            btn_mirror_shape.borrow_mut().activate();
+                            btn_rotate_shape.borrow_mut().activate();
+                        } else {
+                            btn_mirror_shape.borrow_mut().deactivate();
+                            btn_rotate_shape.borrow_mut().deactivate();
+                        }
                     }
-                    rotshape.push(curcolumn);
+                    Err(err) => alert_default(&format!("Could not open {}: {err}", path.display())),
+                }
+            }
+        });
+    }
+
+    {
+        let canvas = canvas.clone();
+        let inp_update_intervall = inp_update_intervall.clone();
+
+        btn_save_session.borrow_mut().set_callback(move |_| {
+            if let Some(mut path) =
+                choose_file(NativeFileChooserType::BrowseSaveFile, SESSIONFILTER)
+            {
+                if path.extension().is_none() {
+                    path.set_extension("session");
+                }
+                let intervall = inp_update_intervall
+                    .borrow()
+                    .value()
+                    .parse()
+                    .unwrap_or(INITIALUPDATEINTERVALL);
+                let res = canvas.borrow().save_session(&path, intervall);
+                if let Err(err) = res {
+                    alert_default(&format!("Could not save {}: {err}", path.display()));
                 }
-                rotshape.reverse();
-                *shape = rotshape;
             }
         });
     }
@@ -334,7 +804,23 @@ fn main() {
 
         let hidewidgets: Vec<Rc<RefCell<dyn WidgetExt>>> = vec![
             btn_step.clone(),
+            btn_back.clone(),
             inp_update_intervall.clone(),
+            inp_rule.clone(),
+            inp_topology.clone(),
+            inp_jump_exponent.clone(),
+            btn_jump.clone(),
+            btn_open.clone(),
+            btn_save.clone(),
+            btn_open_session.clone(),
+            btn_save_session.clone(),
+            btn_copy.clone(),
+            btn_cut.clone(),
+            btn_paste.clone(),
+            btn_delete.clone(),
+            inp_density.clone(),
+            btn_random.clone(),
+            mnu_transform.clone(),
             mnu_shapeselect.clone(),
             btn_mirror_shape.clone(),
             btn_rotate_shape.clone(),
@@ -378,11 +864,12 @@ fn main() {
 
                 handle.set_label("Stop");
 
+                canvas.borrow_mut().start_run();
                 let canvas = canvas.clone();
 
                 let update = move |handle| {
                     let start = std::time::Instant::now();
-                    canvas.borrow_mut().update_threaded(10, 50);
+                    canvas.borrow_mut().update_run();
                     app::repeat_timeout3(intervall - start.elapsed().as_secs_f64(), handle);
                 };
 
@@ -391,40 +878,135 @@ fn main() {
         });
     }
 
+    {
+        let canvas = canvas.clone();
+        let mnu_shapeselect = mnu_shapeselect.clone();
+
+        let poll = move |handle| {
+            if library.borrow().has_changed() {
+                let selected = selectedshape.borrow().clone();
+                let oldshape = selected.as_ref().and_then(|menupath| {
+                    library
+                        .borrow()
+                        .get(menupath)
+                        .map(|file| file.shape.clone())
+                });
+                let errors = library.borrow_mut().reload();
+                let library = library.borrow();
+
+                //the selected shape follows its file, flips and rotations are kept if the file did not change
+                if let Some(menupath) = &selected {
+                    match library.get(menupath) {
+                        Some(file) if Some(&file.shape) != oldshape.as_ref() => {
+                            canvas.borrow_mut().set_curshape(Some(file.shape.clone()))
+                        }
+                        Some(_) => (),
+                        None => {
+                            canvas.borrow_mut().set_curshape(None);
+                            *selectedshape.borrow_mut() = None;
+                            for button in &shapebuttons {
+                                button.borrow_mut().deactivate();
+                            }
+                        }
+                    }
+                }
+                fill_shape_menu(
+                    &mut mnu_shapeselect.borrow_mut(),
+                    &library,
+                    &canvas,
+                    &shapebuttons,
+                    &selectedshape,
+                );
+
+                //only files that changed are loaded again, so errors are not repeated while a file stays broken
+                if !errors.is_empty() {
+                    let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+                    alert_default(&format!(
+                        "Some shapes could not be loaded:\n{}",
+                        errors.join("\n")
+                    ));
+                }
+            }
+            app::repeat_timeout3(SHAPEPOLLTIME, handle);
+        };
+
+        app::add_timeout3(SHAPEPOLLTIME, poll);
+    }
+
     {
         let mut starttime_tick = std::time::Instant::now();
 
         let btn_stop_toggle = btn_stop_toggle.clone();
         let btn_step = btn_step.clone();
+        let btn_back = btn_back.clone();
         let btn_mirror_shape = btn_mirror_shape.clone();
         let btn_rotate_shape = btn_rotate_shape.clone();
+        let inp_rule = inp_rule.clone();
+        let inp_topology = inp_topology.clone();
 
         let tick = move |handle| {
-            let offset = canvas.borrow().offset();
-            let xoffset = offset.0;
-            let yoffset = offset.1;
-            let linedist = canvas.borrow().linedist();
-
             canvas
                 .borrow_mut()
                 .redraw_canvas(btn_drawchunks.borrow().value());
             btn_stop_toggle.borrow_mut().redraw();
             btn_step.borrow_mut().redraw();
+            btn_back.borrow_mut().redraw();
             btn_mirror_shape.borrow_mut().redraw();
             btn_rotate_shape.borrow_mut().redraw();
 
-            let xmod = (app::event_x() + xoffset).rem_euclid(linedist);
-            let ymod = (app::event_y() + yoffset).rem_euclid(linedist);
-
-            let curcellmousepos = (
-                ((app::event_x() + xoffset - xmod) / linedist),
-                ((app::event_y() + yoffset - ymod) / linedist),
+            //rule and topology also change by opening files and undoing, unless they are being edited
+            let rule = canvas.borrow().rule();
+            let mut inp_rule = inp_rule.borrow_mut();
+            if !inp_rule.has_focus() && inp_rule.value().parse::<Rule>().ok() != Some(rule) {
+                inp_rule.set_value(format!("{rule}").as_str());
+                inp_rule.set_text_color(Color::Black);
+            }
+            drop(inp_rule);
+
+            let topology = canvas.borrow().topology();
+            let mut inp_topology = inp_topology.borrow_mut();
+            if !inp_topology.has_focus()
+                && inp_topology.value().parse::<Topology>().ok() != Some(topology)
+            {
+                inp_topology.set_value(format!("{topology}").as_str());
+                inp_topology.set_text_color(Color::Black);
+            }
+            drop(inp_topology);
+
+            let curcellmousepos = canvas.borrow().screen_to_cell(app::event_coords());
+            let zoom = match canvas.borrow().zoomout() {
+                0 => format!("{} px per cell", canvas.borrow().linedist()),
+                zoomout => format!("{0}x{0} cells per px", 1u64 << zoomout),
+            };
+
+            let boundingbox = match canvas.borrow().bounding_box() {
+                Some((min, max)) => format!(
+                    "({}, {}) - ({}, {}), {}x{}",
+                    min.0,
+                    min.1,
+                    max.0,
+                    max.1,
+                    max.0 - min.0 + 1,
+                    max.1 - min.1 + 1
+                ),
+                None => "-".to_string(),
+            };
+
+            lbl_coords.borrow_mut().set_label(
+                format!(
+                    "X: {} Y: {}   Zoom: {}   Generation: {}   Population: {} (+{} -{})   Bounding box: {}",
+                    curcellmousepos.0,
+                    curcellmousepos.1,
+                    zoom,
+                    canvas.borrow().generation(),
+                    canvas.borrow().population(),
+                    canvas.borrow().births(),
+                    canvas.borrow().deaths(),
+                    boundingbox
+                )
+                .as_str(),
             );
 
-            lbl_coords
-                .borrow_mut()
-                .set_label(format!("X: {} Y: {}", curcellmousepos.0, curcellmousepos.1).as_str());
-
             app::repeat_timeout3(TICKTIME - starttime_tick.elapsed().as_secs_f64(), handle);
             starttime_tick = std::time::Instant::now();
         };
@@ -432,5 +1014,18 @@ fn main() {
         app::add_timeout3(TICKTIME, tick);
     }
     wind.show();
+
+    if !configerrors.is_empty() {
+        let errors: Vec<String> = configerrors.iter().map(|err| err.to_string()).collect();
+        alert_default(&format!("Invalid configuration:\n{}", errors.join("\n")));
+    }
+    if !shapeerrors.is_empty() {
+        let errors: Vec<String> = shapeerrors.iter().map(|err| err.to_string()).collect();
+        alert_default(&format!(
+            "Some shapes could not be loaded:\n{}",
+            errors.join("\n")
+        ));
+    }
+
     app.run().unwrap();
 }
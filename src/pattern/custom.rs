@@ -0,0 +1,47 @@
+use crate::field::Shape;
+use crate::pattern::Pattern;
+use crate::transform::Transform;
+
+//0 is a dead cell, 1 an alive one and every other character leaves the cell unchanged
+pub fn parse(bytebuf: &[u8]) -> Pattern {
+    let mut curshape: Shape = Vec::new();
+
+    let bytebuflines = bytebuf
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+    for line in bytebuflines {
+        let mut curline = Vec::new();
+        for b in line {
+            if *b == b'0' {
+                curline.push(Some(0));
+            } else if *b == b'1' {
+                curline.push(Some(1));
+            } else {
+                curline.push(None);
+            }
+        }
+        curshape.push(curline);
+    }
+    //we have to mirror along the (0,0) -- (1,1) diagonal due to how we read the file
+    let curshape = Transform::Transpose.apply(&curshape);
+
+    Pattern::from_shape(&curshape)
+}
+
+pub fn to_string(pattern: &Pattern) -> String {
+    let mut res = String::new();
+    let shape = pattern.to_shape();
+    for y in 0..pattern.height() {
+        for column in &shape {
+            res.push(match column[y] {
+                Some(1) => '1',
+                //the format has no dying states
+                Some(_) => '0',
+                None => ' ',
+            });
+        }
+        res.push('\n');
+    }
+    res
+}
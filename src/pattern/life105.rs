@@ -0,0 +1,125 @@
+use crate::pattern::{Pattern, PatternError};
+use crate::rule::Rule;
+
+//cell blocks in the style of the .cells format, each placed at the position of the preceding #P line
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut cells = Vec::new();
+    let mut blockpos = (0, 0);
+    let mut y = 0;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let text = chars.as_str().trim();
+            match kind {
+                Some('D' | 'C') => pattern.comments.push(text.to_string()),
+                Some('N') => pattern.rule = Some(Rule::conway()),
+                Some('R') => pattern.rule = Some(text.parse()?),
+                Some('P') => {
+                    let mut coords = text.split_whitespace().map(|c| c.parse::<isize>());
+                    match (coords.next(), coords.next()) {
+                        (Some(Ok(x)), Some(Ok(y))) => blockpos = (x, y),
+                        _ => return Err(PatternError::InvalidHeader(line.to_string())),
+                    }
+                    y = 0;
+                }
+                //the #Life 1.05 header itself
+                Some('L') => (),
+                _ => return Err(PatternError::InvalidHeader(line.to_string())),
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '.' => (),
+                '*' | 'O' => cells.push((blockpos.0 + x as isize, blockpos.1 + y)),
+                c => return Err(PatternError::InvalidToken(c)),
+            }
+        }
+        y += 1;
+    }
+
+    pattern.set_cells(&cells);
+    Ok(pattern)
+}
+
+pub fn to_string(pattern: &Pattern) -> String {
+    let mut res = String::from("#Life 1.05\n");
+
+    for comment in pattern.name.iter().chain(&pattern.comments) {
+        res.push_str(&format!("#D {comment}\n"));
+    }
+    match pattern.rule {
+        //there is no traditional notation for non-totalistic rules
+        Some(rule) if !rule.is_totalistic() => res.push_str(&format!("#R {rule}\n")),
+        Some(rule) if rule != Rule::conway() => {
+            //the format uses the traditional survival/birth notation, generations rules add /states
+            let counts = |f: &dyn Fn(u8) -> bool| -> String {
+                (0..=8).filter(|n| f(*n)).map(|n| n.to_string()).collect()
+            };
+            let survival = counts(&|n| rule.survival(n));
+            let birth = counts(&|n| rule.birth(n));
+            match rule.states() {
+                2 => res.push_str(&format!("#R {survival}/{birth}\n")),
+                states => res.push_str(&format!("#R {survival}/{birth}/{states}\n")),
+            }
+        }
+        _ => res.push_str("#N\n"),
+    }
+
+    let origin = pattern.origin.unwrap_or((0, 0));
+    res.push_str(&format!("#P {} {}\n", origin.0, origin.1));
+    for row in pattern.alive_rows() {
+        if row.is_empty() {
+            res.push('.');
+        }
+        res.extend(row.iter().map(|cell| if *cell { '*' } else { '.' }));
+        res.push('\n');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_blocks_at_their_positions() {
+        let text = "#Life 1.05\n#D Two blocks\n#R 23/36\n#P -1 -1\n**\n**\n#P 3 2\n.*\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.comments, ["Two blocks"]);
+        assert_eq!(pattern.rule, Some("B36/S23".parse().unwrap()));
+        assert_eq!(pattern.origin, Some((-1, -1)));
+        assert_eq!(pattern.size, (6, 4));
+        let alive: Vec<(usize, usize)> = pattern.cells.iter().map(|(pos, _)| *pos).collect();
+        assert_eq!(alive, [(0, 0), (1, 0), (0, 1), (1, 1), (5, 3)]);
+    }
+
+    #[test]
+    fn round_trips_through_to_string() {
+        for rule in ["B3/S23", "B36/S23", "B2/S/C4", "B2-a/S12"] {
+            let mut pattern = Pattern {
+                rule: Some(rule.parse().unwrap()),
+                ..Default::default()
+            };
+            pattern.set_cells(&[(-3, 4), (-1, 4), (0, 6)]);
+            let reparsed = parse(&to_string(&pattern)).unwrap();
+            assert_eq!(reparsed.rule, pattern.rule);
+            assert_eq!(reparsed.origin, pattern.origin);
+            assert_eq!(reparsed.cells, pattern.cells);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_directives() {
+        assert!(parse("#Life 1.05\n#é\n*\n").is_err());
+        assert!(parse("#Life 1.05\n#\n*\n").is_err());
+        assert!(parse("#Life 1.05\n#P 1\n*\n").is_err());
+        assert!(parse("#Life 1.05\n*x\n").is_err());
+    }
+}
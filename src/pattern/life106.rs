@@ -0,0 +1,79 @@
+use crate::pattern::{Pattern, PatternError};
+
+//one line with the coordinates of every alive cell
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut cells = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if !comment.starts_with("Life") {
+                pattern.comments.push(comment.trim().to_string());
+            }
+            continue;
+        }
+
+        let mut coords = line.split_whitespace().map(|c| c.parse::<isize>());
+        match (coords.next(), coords.next(), coords.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => cells.push((x, y)),
+            _ => return Err(PatternError::InvalidHeader(line.to_string())),
+        }
+    }
+
+    pattern.set_cells(&cells);
+    Ok(pattern)
+}
+
+pub fn to_string(pattern: &Pattern) -> String {
+    let mut res = String::from("#Life 1.06\n");
+    let origin = pattern.origin.unwrap_or((0, 0));
+
+    let mut cells: Vec<(usize, usize)> = pattern
+        .cells
+        .iter()
+        .filter(|(_, state)| *state == 1)
+        .map(|(pos, _)| *pos)
+        .collect();
+    cells.sort_unstable();
+    for (x, y) in cells {
+        res.push_str(&format!(
+            "{} {}\n",
+            origin.0 + x as isize,
+            origin.1 + y as isize
+        ));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_to_string() {
+        let text = "#Life 1.06\n-2 5\n0 3\n0 4\n7 -1\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.origin, Some((-2, -1)));
+        assert_eq!(pattern.size, (10, 7));
+        assert_eq!(pattern.cells.len(), 4);
+        assert_eq!(to_string(&pattern), text);
+    }
+
+    #[test]
+    fn keeps_distant_cells_sparse() {
+        let pattern = parse("#Life 1.06\n0 0\n1000000000000 -1000000000000\n").unwrap();
+        assert_eq!(pattern.size, (1000000000001, 1000000000001));
+        assert_eq!(pattern.cells.len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(parse("#Life 1.06\n1\n").is_err());
+        assert!(parse("#Life 1.06\n1 2 3\n").is_err());
+        assert!(parse("#Life 1.06\n1 é\n").is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::hashlife::{HashLife, NodeId, ALIVE, DEAD, MAXLEVEL};
+use crate::pattern::{Pattern, PatternError};
+
+//nodes of this level are written as cells instead of references to their children
+const LEAFLEVEL: u8 = 3;
+const LEAFSIZE: usize = 1 << LEAFLEVEL;
+
+fn node_from_cells(
+    hashlife: &mut HashLife,
+    cells: &[[bool; LEAFSIZE]; LEAFSIZE],
+    (x, y): (usize, usize),
+    level: u8,
+) -> NodeId {
+    if level == 0 {
+        return if cells[y][x] { ALIVE } else { DEAD };
+    }
+    let half = 1 << (level - 1);
+    let children = [(x, y), (x + half, y), (x, y + half), (x + half, y + half)]
+        .map(|corner| node_from_cells(hashlife, cells, corner, level - 1));
+    hashlife.join(children)
+}
+
+fn cells_from_node(
+    hashlife: &HashLife,
+    id: NodeId,
+    cells: &mut [[bool; LEAFSIZE]; LEAFSIZE],
+    (x, y): (usize, usize),
+) {
+    if hashlife.population_of(id) == 0 {
+        return;
+    }
+    if hashlife.level(id) == 0 {
+        cells[y][x] = true;
+        return;
+    }
+    let half = 1 << (hashlife.level(id) - 1);
+    let children = hashlife.children(id);
+    for (child, corner) in
+        children
+            .into_iter()
+            .zip([(x, y), (x + half, y), (x, y + half), (x + half, y + half)])
+    {
+        cells_from_node(hashlife, child, cells, corner);
+    }
+}
+
+//golly's macrocell format, a list of quadtree nodes where every node refers to earlier ones by their line number
+pub fn parse(text: &str) -> Result<HashLife, PatternError> {
+    let mut hashlife = HashLife::new();
+    //index 0 stands for an empty node of whatever level is needed
+    let mut nodes: Vec<Option<NodeId>> = vec![None];
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('[') {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let text = chars.as_str();
+            match kind {
+                Some('R') => hashlife.set_rule(text.trim().parse()?),
+                Some('G') => {
+                    let generation = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| PatternError::InvalidHeader(line.to_string()))?;
+                    hashlife.set_generation(generation);
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        if line.starts_with(['.', '*', '$']) {
+            let mut cells = [[false; LEAFSIZE]; LEAFSIZE];
+            let (mut x, mut y) = (0, 0);
+            for c in line.chars() {
+                match c {
+                    '.' => x += 1,
+                    '*' => {
+                        if x >= LEAFSIZE || y >= LEAFSIZE {
+                            return Err(PatternError::InvalidHeader(line.to_string()));
+                        }
+                        cells[y][x] = true;
+                        x += 1;
+                    }
+                    '$' => {
+                        x = 0;
+                        y += 1;
+                    }
+                    c => return Err(PatternError::InvalidToken(c)),
+                }
+            }
+            let node = node_from_cells(&mut hashlife, &cells, (0, 0), LEAFLEVEL);
+            nodes.push(Some(node));
+            continue;
+        }
+
+        let invalid = || PatternError::InvalidHeader(line.to_string());
+        let numbers: Vec<usize> = line
+            .split_whitespace()
+            .map(|n| n.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let [level, ref refs @ ..] = numbers[..] else {
+            return Err(invalid());
+        };
+        if refs.len() != 4 || level <= LEAFLEVEL as usize || level > MAXLEVEL as usize {
+            return Err(invalid());
+        }
+
+        let mut children = [DEAD; 4];
+        for (child, index) in children.iter_mut().zip(refs.iter().copied()) {
+            let node = nodes.get(index).copied().ok_or_else(invalid)?;
+            *child = match node {
+                Some(node) if hashlife.level(node) as usize == level - 1 => node,
+                Some(_) => return Err(invalid()),
+                None => hashlife.empty(level as u8 - 1),
+            };
+        }
+        let node = hashlife.join(children);
+        nodes.push(Some(node));
+    }
+
+    if let Some(Some(root)) = nodes.last() {
+        hashlife.set_root(*root);
+    }
+    Ok(hashlife)
+}
+
+fn write_node(
+    hashlife: &HashLife,
+    id: NodeId,
+    indices: &mut HashMap<NodeId, usize>,
+    lines: &mut Vec<String>,
+) -> usize {
+    if hashlife.population_of(id) == 0 {
+        return 0;
+    }
+    if let Some(index) = indices.get(&id) {
+        return *index;
+    }
+
+    let line = if hashlife.level(id) == LEAFLEVEL {
+        let mut cells = [[false; LEAFSIZE]; LEAFSIZE];
+        cells_from_node(hashlife, id, &mut cells, (0, 0));
+
+        let mut line = String::new();
+        for row in cells {
+            let len = row
+                .iter()
+                .rposition(|cell| *cell)
+                .map_or(0, |last| last + 1);
+            line.extend(row[..len].iter().map(|cell| if *cell { '*' } else { '.' }));
+            line.push('$');
+        }
+        //empty rows at the end can be left out
+        line.truncate(line.trim_end_matches('$').len() + 1);
+        line
+    } else {
+        let children = hashlife
+            .children(id)
+            .map(|child| write_node(hashlife, child, indices, lines));
+        format!(
+            "{} {} {} {} {}",
+            hashlife.level(id),
+            children[0],
+            children[1],
+            children[2],
+            children[3]
+        )
+    };
+
+    lines.push(line);
+    indices.insert(id, lines.len());
+    lines.len()
+}
+
+pub fn to_string(hashlife: &HashLife) -> String {
+    let mut res = String::from("[M2] (game-of-life)\n");
+    res.push_str(&format!("#R {}\n", hashlife.rule()));
+    if hashlife.generation() > 0 {
+        res.push_str(&format!("#G {}\n", hashlife.generation()));
+    }
+
+    let mut lines = Vec::new();
+    write_node(hashlife, hashlife.root(), &mut HashMap::new(), &mut lines);
+    for line in lines {
+        res.push_str(&line);
+        res.push('\n');
+    }
+    res
+}
+
+pub fn to_pattern(hashlife: &HashLife) -> Pattern {
+    let mut pattern = Pattern {
+        rule: Some(hashlife.rule()),
+        generation: Some(hashlife.generation()),
+        ..Default::default()
+    };
+    pattern.set_cells(&hashlife.alive_cells());
+    pattern
+}
+
+pub fn from_pattern(pattern: &Pattern) -> HashLife {
+    let mut hashlife = HashLife::new();
+    if let Some(rule) = pattern.rule {
+        hashlife.set_rule(rule);
+    }
+    hashlife.set_generation(pattern.generation.unwrap_or(0));
+    let origin = pattern.origin.unwrap_or((0, 0));
+    for ((x, y), state) in &pattern.cells {
+        hashlife.set_cell(
+            (origin.0 + *x as isize, origin.1 + *y as isize),
+            *state == 1,
+        );
+    }
+    hashlife
+}
+
+pub fn read_file(path: &Path) -> Result<HashLife, PatternError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn write_file(path: &Path, hashlife: &HashLife) -> Result<(), PatternError> {
+    fs::write(path, to_string(hashlife))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Format;
+    use crate::rule::Rule;
+
+    fn sorted_cells(hashlife: &HashLife) -> Vec<(isize, isize)> {
+        let mut cells = hashlife.alive_cells();
+        cells.sort_unstable();
+        cells
+    }
+
+    //the cells relative to the upper left corner of their bounding box
+    fn normalized(mut cells: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
+        let minx = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let miny = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        for cell in &mut cells {
+            *cell = (cell.0 - minx, cell.1 - miny);
+        }
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn parses_golly_glider() {
+        let hashlife = parse("[M2] (golly 4.2)\n#R B3/S23\n.*$..*$***$\n").unwrap();
+        assert_eq!(hashlife.rule(), Rule::conway());
+        assert_eq!(
+            normalized(hashlife.alive_cells()),
+            [(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn round_trips_sparse_patterns() {
+        let mut hashlife = HashLife::new();
+        for coords in [(0, 0), (1, 0), (-1000, 5), (3000, -7000), (3001, -7000)] {
+            hashlife.set_cell(coords, true);
+        }
+        let reparsed = parse(&to_string(&hashlife)).unwrap();
+        assert_eq!(sorted_cells(&reparsed), sorted_cells(&hashlife));
+    }
+
+    #[test]
+    fn round_trips_rule_and_generation() {
+        let mut hashlife = HashLife::new();
+        hashlife.set_rule("B36/S23".parse().unwrap());
+        for coords in [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)] {
+            hashlife.set_cell(coords, true);
+        }
+        hashlife.jump(7).unwrap();
+        let text = to_string(&hashlife);
+        assert!(text.starts_with("[M2]"));
+        let reparsed = parse(&text).unwrap();
+        assert_eq!(reparsed.rule(), hashlife.rule());
+        assert_eq!(reparsed.generation(), 128);
+        assert_eq!(sorted_cells(&reparsed), sorted_cells(&hashlife));
+    }
+
+    #[test]
+    fn keeps_distant_cells_sparse() {
+        let mut hashlife = HashLife::new();
+        hashlife.set_cell((-(1 << 30), 0), true);
+        hashlife.set_cell((1 << 30, 1 << 30), true);
+        let pattern = Format::Macrocell
+            .parse(to_string(&hashlife).as_bytes())
+            .unwrap();
+        assert_eq!(pattern.origin, Some((-(1 << 30), 0)));
+        assert_eq!(pattern.size, ((1 << 31) + 1, (1 << 30) + 1));
+        assert_eq!(pattern.cells.len(), 2);
+        assert_eq!(
+            sorted_cells(&from_pattern(&pattern)),
+            sorted_cells(&hashlife)
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_directives() {
+        let hashlife = parse("[M2]\n#\n#é ü\n#C comment\n.*$\n").unwrap();
+        assert_eq!(hashlife.alive_cells().len(), 1);
+        assert!(parse("[M2]\n#Gé\n.*$\n").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_nodes() {
+        assert!(parse("[M2]\n4 1 0 0 0\n").is_err());
+        assert!(parse("[M2]\n.*$x\n").is_err());
+    }
+}
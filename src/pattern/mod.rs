@@ -0,0 +1,338 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::field::{Field, Shape, State};
+use crate::rule::{ParseRuleError, Rule};
+use crate::topology::{ParseTopologyError, Topology};
+
+pub mod custom;
+pub mod life105;
+pub mod life106;
+pub mod macrocell;
+pub mod plaintext;
+pub mod rle;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    //the 0/1 format of the files in ./shapes/
+    Custom,
+    Rle,
+    //the .cells format
+    Plaintext,
+    Life105,
+    Life106,
+    //golly's quadtree format, suited for huge patterns
+    Macrocell,
+}
+
+//a pattern together with the information pattern files can carry besides the cells
+#[derive(Default)]
+pub struct Pattern {
+    //the cells that are set, relative to the origin, cells within the size that are not listed are dead
+    pub cells: Vec<((usize, usize), State)>,
+    //width and height, which can reach beyond the cells
+    pub size: (usize, usize),
+    //custom shapes leave the cells they don't list unchanged instead
+    pub transparent: bool,
+    pub rule: Option<Rule>,
+    //only finite universes are stored
+    pub topology: Option<Topology>,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+    //position of the upper left corner in the field, if the file stores one
+    pub origin: Option<(isize, isize)>,
+    pub generation: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    InvalidHeader(String),
+    InvalidToken(char),
+    InvalidRule(ParseRuleError),
+    InvalidTopology(ParseTopologyError),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(err) => write!(f, "{err}"),
+            PatternError::InvalidHeader(header) => write!(f, "invalid header \"{header}\""),
+            PatternError::InvalidToken(c) => write!(f, "unexpected character '{c}'"),
+            PatternError::InvalidRule(err) => write!(f, "{err}"),
+            PatternError::InvalidTopology(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatternError::Io(err) => Some(err),
+            PatternError::InvalidRule(err) => Some(err),
+            PatternError::InvalidTopology(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PatternError {
+    fn from(err: io::Error) -> Self {
+        PatternError::Io(err)
+    }
+}
+
+impl From<ParseRuleError> for PatternError {
+    fn from(err: ParseRuleError) -> Self {
+        PatternError::InvalidRule(err)
+    }
+}
+
+impl From<ParseTopologyError> for PatternError {
+    fn from(err: ParseTopologyError) -> Self {
+        PatternError::InvalidTopology(err)
+    }
+}
+
+impl Pattern {
+    //the alive and dying cells of the field within their bounding box, with position, rule and generation
+    pub fn from_field(field: &Field) -> Self {
+        let mut pattern = Self {
+            rule: Some(field.rule()),
+            topology: Some(field.topology()).filter(Topology::is_finite),
+            generation: Some(field.generation()),
+            ..Default::default()
+        };
+        if let Some((min, max)) = field.bounding_box() {
+            pattern.cells = field
+                .cells_in(min, max)
+                .into_iter()
+                .map(|((x, y), state)| (((x - min.0) as usize, (y - min.1) as usize), state))
+                .collect();
+            pattern.size = ((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize);
+            pattern.origin = Some(min);
+        }
+        pattern
+    }
+
+    //the cells of a shape, it stays transparent where it doesn't set cells
+    pub fn from_shape(shape: &Shape) -> Self {
+        let transparent = shape.iter().flatten().any(Option::is_none);
+        let cells = shape
+            .iter()
+            .enumerate()
+            .flat_map(|(x, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(y, cell)| cell.map(|state| ((x, y), state)))
+            })
+            .filter(|(_, state)| transparent || *state != 0)
+            .collect();
+        Self {
+            cells,
+            size: (shape.len(), height(shape)),
+            transparent,
+            ..Default::default()
+        }
+    }
+
+    //the cells within the size, only meant for patterns small enough to be placed as a shape
+    pub fn to_shape(&self) -> Shape {
+        let background = if self.transparent { None } else { Some(0) };
+        let mut shape = vec![vec![background; self.size.1]; self.size.0];
+        for ((x, y), state) in &self.cells {
+            shape[*x][*y] = Some(*state);
+        }
+        shape
+    }
+
+    //replaces the content of the field with the pattern
+    pub fn load_into(&self, field: &mut Field) {
+        field.clear();
+        if let Some(rule) = self.rule {
+            field.set_rule(rule);
+        }
+        if let Some(topology) = self.topology {
+            field.set_topology(topology);
+        }
+        let origin = self.origin.unwrap_or((0, 0));
+        for ((x, y), state) in &self.cells {
+            field.set_state((origin.0 + *x as isize, origin.1 + *y as isize), *state);
+        }
+        field.set_generation(self.generation.unwrap_or(0));
+    }
+
+    //coordinates of alive cells, the origin gets set to the upper left corner of their bounding box
+    pub fn set_cells(&mut self, cells: &[(isize, isize)]) {
+        let (Some(minx), Some(miny)) = (
+            cells.iter().map(|c| c.0).min(),
+            cells.iter().map(|c| c.1).min(),
+        ) else {
+            self.cells = Vec::new();
+            self.size = (0, 0);
+            return;
+        };
+        let maxx = cells.iter().map(|c| c.0).max().unwrap();
+        let maxy = cells.iter().map(|c| c.1).max().unwrap();
+
+        self.cells = cells
+            .iter()
+            .map(|(x, y)| (((x - minx) as usize, (y - miny) as usize), 1))
+            .collect();
+        self.size = ((maxx - minx + 1) as usize, (maxy - miny + 1) as usize);
+        self.origin = Some((minx, miny));
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.0
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.1
+    }
+
+    //the alive cells row by row, formats without dying cells store them as dead
+    pub fn alive_rows(&self) -> Vec<Vec<bool>> {
+        let mut rows = vec![Vec::new(); self.size.1];
+        for ((x, y), state) in &self.cells {
+            let row = &mut rows[*y];
+            if *state == 1 {
+                if row.len() <= *x {
+                    row.resize(x + 1, false);
+                }
+                row[*x] = true;
+            }
+        }
+        rows
+    }
+}
+
+fn height(shape: &Shape) -> usize {
+    shape.iter().map(|column| column.len()).max().unwrap_or(0)
+}
+
+impl Format {
+    //guesses the format from the first lines of a file
+    pub fn sniff(text: &str) -> Self {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let first = lines.next().unwrap_or("");
+
+        if first.starts_with("[M2]") {
+            Format::Macrocell
+        } else if first.starts_with("#Life 1.06") {
+            Format::Life106
+        } else if first.starts_with("#Life 1.05") {
+            Format::Life105
+        } else if text.lines().all(|line| {
+            line.starts_with('!') || line.trim_end().chars().all(|c| c == '.' || c == 'O')
+        }) {
+            Format::Plaintext
+        } else if std::iter::once(first)
+            .chain(lines)
+            .find(|line| !line.starts_with('#'))
+            .is_some_and(|line| line.starts_with('x') && line.contains('='))
+        {
+            Format::Rle
+        } else {
+            Format::Custom
+        }
+    }
+
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rle" => Some(Format::Rle),
+            "cells" => Some(Format::Plaintext),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    //the extension decides if it is unambiguous, otherwise the content does
+    pub fn detect(path: &Path, text: &str) -> Self {
+        Self::from_extension(path).unwrap_or_else(|| Self::sniff(text))
+    }
+
+    pub fn parse(&self, bytes: &[u8]) -> Result<Pattern, PatternError> {
+        let text = String::from_utf8_lossy(bytes);
+        match self {
+            Format::Custom => Ok(custom::parse(bytes)),
+            Format::Rle => rle::parse(&text),
+            Format::Plaintext => plaintext::parse(&text),
+            Format::Life105 => life105::parse(&text),
+            Format::Life106 => life106::parse(&text),
+            Format::Macrocell => {
+                macrocell::parse(&text).map(|hashlife| macrocell::to_pattern(&hashlife))
+            }
+        }
+    }
+
+    pub fn serialize(&self, pattern: &Pattern) -> String {
+        match self {
+            Format::Custom => custom::to_string(pattern),
+            Format::Rle => rle::to_string(pattern),
+            Format::Plaintext => plaintext::to_string(pattern),
+            Format::Life105 => life105::to_string(pattern),
+            Format::Life106 => life106::to_string(pattern),
+            Format::Macrocell => macrocell::to_string(&macrocell::from_pattern(pattern)),
+        }
+    }
+}
+
+pub fn is_macrocell(path: &Path) -> bool {
+    Format::from_extension(path) == Some(Format::Macrocell)
+}
+
+pub fn read_file(path: &Path) -> Result<Pattern, PatternError> {
+    //Reads entire file into buffer, not a great idea for huge files
+    let bytes = fs::read(path)?;
+    let format = Format::detect(path, &String::from_utf8_lossy(&bytes));
+    format.parse(&bytes)
+}
+
+//the format is chosen by the extension, .lif and .life files are written as life 1.06
+fn write_format(path: &Path) -> Format {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("lif" | "life") => Format::Life106,
+        _ => Format::from_extension(path).unwrap_or(Format::Rle),
+    }
+}
+
+pub fn write_file(path: &Path, pattern: &Pattern) -> Result<(), PatternError> {
+    fs::write(path, write_format(path).serialize(pattern))?;
+    Ok(())
+}
+
+pub fn write_field(path: &Path, field: &Field) -> Result<(), PatternError> {
+    write_file(path, &Pattern::from_field(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_formats() {
+        assert_eq!(
+            Format::sniff("[M2] (golly)\n#R B3/S23\n"),
+            Format::Macrocell
+        );
+        assert_eq!(Format::sniff("#Life 1.06\n0 0\n"), Format::Life106);
+        assert_eq!(Format::sniff("#Life 1.05\n*\n"), Format::Life105);
+        assert_eq!(
+            Format::sniff("!Name: Glider\n.O\n..O\nOOO\n"),
+            Format::Plaintext
+        );
+        assert_eq!(Format::sniff(".O\n..O\nOOO\n"), Format::Plaintext);
+        assert_eq!(
+            Format::sniff("#N Glider\nx = 3, y = 3\nbo$2bo$3o!\n"),
+            Format::Rle
+        );
+        assert_eq!(Format::sniff("010\n001\n111\n"), Format::Custom);
+        //a leading comment alone doesn't make it a .cells file
+        assert_eq!(Format::sniff("!\n010\n001\n111\n"), Format::Custom);
+    }
+}
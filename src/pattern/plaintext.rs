@@ -0,0 +1,79 @@
+use crate::pattern::{Pattern, PatternError};
+
+//the .cells format: lines starting with ! are comments, . is a dead cell and O an alive one
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut y = 0;
+
+    for line in text.lines() {
+        let line = line.trim_end();
+
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.strip_prefix("Name:") {
+                Some(name) => pattern.name = Some(name.trim().to_string()),
+                None => pattern.comments.push(comment.trim().to_string()),
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '.' => (),
+                'O' | '*' => pattern.cells.push(((x, y), 1)),
+                c => return Err(PatternError::InvalidToken(c)),
+            }
+            pattern.size.0 = pattern.size.0.max(x + 1);
+        }
+        y += 1;
+    }
+
+    pattern.size.1 = y;
+    Ok(pattern)
+}
+
+pub fn to_string(pattern: &Pattern) -> String {
+    let mut res = String::new();
+
+    if let Some(name) = &pattern.name {
+        res.push_str(&format!("!Name: {name}\n"));
+    }
+    for comment in &pattern.comments {
+        res.push_str(&format!("!{comment}\n"));
+    }
+    for row in pattern.alive_rows() {
+        res.extend(row.iter().map(|cell| if *cell { 'O' } else { '.' }));
+        res.push('\n');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "!Name: Glider\n!A comment\n.O\n..O\nOOO\n";
+
+    #[test]
+    fn parses_glider() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.comments, ["A comment"]);
+        assert_eq!(pattern.size, (3, 3));
+        let alive: Vec<(usize, usize)> = pattern.cells.iter().map(|(pos, _)| *pos).collect();
+        assert_eq!(alive, [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn round_trips_through_to_string() {
+        assert_eq!(to_string(&parse(GLIDER).unwrap()), GLIDER);
+        //empty rows are kept, trailing dead cells are not
+        let text = "O\n\n..O\n";
+        assert_eq!(to_string(&parse("O..\n...\n..O\n").unwrap()), text);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(parse(".O\nx\n").is_err());
+        assert!(parse("Oé\n").is_err());
+    }
+}
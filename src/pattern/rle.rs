@@ -0,0 +1,387 @@
+use crate::field::State;
+use crate::pattern::{Pattern, PatternError};
+use crate::topology::Topology;
+
+//maximum line length of written files, as recommended by the format
+const MAXLINELENGTH: usize = 70;
+
+fn parse_header(
+    line: &str,
+    size: &mut (usize, usize),
+    pattern: &mut Pattern,
+) -> Result<(), PatternError> {
+    let invalid = || PatternError::InvalidHeader(line.to_string());
+
+    let mut entries = line.split(',');
+    while let Some(entry) = entries.next() {
+        let (key, value) = entry.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim();
+        match key.trim() {
+            "x" => size.0 = value.parse().map_err(|_| invalid())?,
+            "y" => size.1 = value.parse().map_err(|_| invalid())?,
+            //golly appends the topology after a colon, its size contains a comma as well
+            "rule" => {
+                let value = entries
+                    .by_ref()
+                    .fold(value.to_string(), |value, entry| value + "," + entry);
+                let (rule, topology) = value.split_once(':').unwrap_or((&value, ""));
+                pattern.rule = Some(rule.parse()?);
+                pattern.topology = Some(topology.parse::<Topology>()?).filter(Topology::is_finite);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(())
+}
+
+fn parse_comment(line: &str, pattern: &mut Pattern) -> Result<(), PatternError> {
+    let mut chars = line.strip_prefix('#').unwrap_or(line).chars();
+    let kind = chars
+        .next()
+        .ok_or_else(|| PatternError::InvalidHeader(line.to_string()))?;
+    let text = chars.as_str().trim();
+
+    match kind {
+        'N' => pattern.name = Some(text.to_string()),
+        'C' | 'c' | 'O' => match text.strip_prefix("XRLE") {
+            //extended rle as written by golly, stores position and generation
+            Some(xrle) => {
+                for entry in xrle.split_whitespace() {
+                    match entry.split_once('=') {
+                        Some(("Pos", pos)) => {
+                            let (x, y) = pos
+                                .split_once(',')
+                                .ok_or_else(|| PatternError::InvalidHeader(line.to_string()))?;
+                            pattern.origin = x.parse().ok().zip(y.parse().ok());
+                        }
+                        Some(("Gen", generation)) => pattern.generation = generation.parse().ok(),
+                        _ => (),
+                    }
+                }
+            }
+            None => pattern.comments.push(text.to_string()),
+        },
+        'P' | 'R' => {
+            let mut coords = text.split_whitespace().map(|c| c.parse().ok());
+            if let (Some(Some(x)), Some(Some(y))) = (coords.next(), coords.next()) {
+                pattern.origin = Some((x, y));
+            }
+        }
+        'r' => pattern.rule = Some(text.parse()?),
+        _ => (),
+    }
+    Ok(())
+}
+
+//only the cells that are not dead are stored, runs may not reach beyond the size in the header
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut size = None;
+
+    //the position of the next cell
+    let mut pos = (0, 0);
+    let mut runcount: Option<usize> = None;
+    //the p to y before a letter of the states above 24
+    let mut prefix: Option<char> = None;
+
+    'lines: for line in text.lines() {
+        let line = line.trim();
+
+        let Some((width, height)) = size else {
+            if line.starts_with('#') {
+                parse_comment(line, &mut pattern)?;
+            } else if line.starts_with('x') {
+                let mut header = (0, 0);
+                parse_header(line, &mut header, &mut pattern)?;
+                size = Some(header);
+            } else if !line.is_empty() {
+                return Err(PatternError::InvalidHeader(line.to_string()));
+            }
+            continue;
+        };
+
+        for c in line.chars() {
+            if prefix.is_some() && !c.is_ascii_uppercase() {
+                return Err(PatternError::InvalidToken(c));
+            }
+            match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap() as usize;
+                    runcount = runcount
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|count| count.checked_add(digit))
+                        .filter(|count| *count <= width.max(height))
+                        .map(Some)
+                        .ok_or(PatternError::InvalidToken(c))?;
+                }
+                'b' | '.' | 'o' | 'A'..='X' => {
+                    let state = match c {
+                        'b' | '.' => 0,
+                        'o' => 1,
+                        c => parse_state(prefix.take(), c)?,
+                    };
+                    let end = pos.0 + runcount.take().unwrap_or(1);
+                    if end > width || pos.1 >= height {
+                        return Err(PatternError::InvalidToken(c));
+                    }
+                    if state != 0 {
+                        pattern
+                            .cells
+                            .extend((pos.0..end).map(|x| ((x, pos.1), state)));
+                    }
+                    pos.0 = end;
+                }
+                'p'..='y' => prefix = Some(c),
+                '$' => {
+                    //a trailing $ may end up just below the last row
+                    pos = (0, pos.1 + runcount.take().unwrap_or(1));
+                    if pos.1 > height {
+                        return Err(PatternError::InvalidToken(c));
+                    }
+                }
+                '!' => break 'lines,
+                c if c.is_whitespace() => (),
+                c => return Err(PatternError::InvalidToken(c)),
+            }
+        }
+    }
+
+    pattern.size = size.unwrap_or((0, 0));
+    Ok(pattern)
+}
+
+//golly's letters for the states of multi-state patterns, A to X and then pA to yO
+fn parse_state(prefix: Option<char>, letter: char) -> Result<State, PatternError> {
+    let high = prefix.map_or(0, |prefix| prefix as u32 - 'p' as u32 + 1);
+    State::try_from(high * 24 + letter as u32 - 'A' as u32 + 1)
+        .map_err(|_| PatternError::InvalidToken(letter))
+}
+
+fn state_tag(state: State, multistate: bool) -> String {
+    match (state, multistate) {
+        (0, false) => "b".to_string(),
+        (1, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state, _) => {
+            let (high, low) = ((state - 1) / 24, (state - 1) % 24);
+            let letter = (b'A' + low) as char;
+            if high == 0 {
+                letter.to_string()
+            } else {
+                format!("{}{letter}", (b'p' + high - 1) as char)
+            }
+        }
+    }
+}
+
+//collects tokens into lines that do not exceed the maximum line length
+struct LineWriter {
+    res: String,
+    curline: String,
+}
+
+impl LineWriter {
+    fn push_run(&mut self, count: usize, tag: &str) {
+        let token = match count {
+            0 => return,
+            1 => tag.to_string(),
+            count => format!("{count}{tag}"),
+        };
+        if self.curline.len() + token.len() > MAXLINELENGTH {
+            self.res.push_str(&self.curline);
+            self.res.push('\n');
+            self.curline.clear();
+        }
+        self.curline.push_str(&token);
+    }
+}
+
+//the comments and the header line
+fn write_header(pattern: &Pattern) -> String {
+    let mut res = String::new();
+
+    if let Some(name) = &pattern.name {
+        res.push_str(&format!("#N {name}\n"));
+    }
+    for comment in &pattern.comments {
+        res.push_str(&format!("#C {comment}\n"));
+    }
+    if pattern.origin.is_some() || pattern.generation.is_some() {
+        res.push_str("#CXRLE");
+        if let Some((x, y)) = pattern.origin {
+            res.push_str(&format!(" Pos={x},{y}"));
+        }
+        if let Some(generation) = pattern.generation {
+            res.push_str(&format!(" Gen={generation}"));
+        }
+        res.push('\n');
+    }
+
+    res.push_str(&format!("x = {}, y = {}", pattern.size.0, pattern.size.1));
+    if let Some(rule) = pattern.rule {
+        res.push_str(&format!(", rule = {rule}"));
+        if let Some(topology) = pattern.topology.filter(Topology::is_finite) {
+            res.push_str(&format!(":{topology}"));
+        }
+    }
+    res.push('\n');
+    res
+}
+
+//encodes the alive and dying cells, sorted by row and then by column and relative to the upper left corner
+fn write_cells(
+    res: String,
+    cells: impl Iterator<Item = ((usize, usize), State)>,
+    multistate: bool,
+) -> String {
+    let mut writer = LineWriter {
+        res,
+        curline: String::new(),
+    };
+    //the next position to write and the run of equal cells that ends there
+    let mut pos = (0, 0);
+    let mut run: Option<(State, usize)> = None;
+
+    for ((x, y), state) in cells {
+        if y > pos.1 || x > pos.0 {
+            if let Some((runstate, count)) = run.take() {
+                writer.push_run(count, &state_tag(runstate, multistate));
+            }
+        }
+        if y > pos.1 {
+            writer.push_run(y - pos.1, "$");
+            pos = (0, y);
+        }
+        writer.push_run(x - pos.0, &state_tag(0, multistate));
+        run = match run {
+            Some((runstate, count)) if runstate == state => Some((runstate, count + 1)),
+            Some((runstate, count)) => {
+                writer.push_run(count, &state_tag(runstate, multistate));
+                Some((state, 1))
+            }
+            None => Some((state, 1)),
+        };
+        pos = (x + 1, y);
+    }
+    if let Some((runstate, count)) = run {
+        writer.push_run(count, &state_tag(runstate, multistate));
+    }
+    writer.push_run(1, "!");
+
+    let mut res = writer.res;
+    res.push_str(&writer.curline);
+    res.push('\n');
+    res
+}
+
+pub fn to_string(pattern: &Pattern) -> String {
+    let res = write_header(pattern);
+    let multistate = pattern.cells.iter().any(|(_, state)| *state > 1);
+    let mut cells: Vec<_> = pattern
+        .cells
+        .iter()
+        .filter(|(_, state)| *state != 0)
+        .copied()
+        .collect();
+    cells.sort_unstable_by_key(|((x, y), _)| (*y, *x));
+    write_cells(res, cells.into_iter(), multistate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field;
+    use crate::rule::Rule;
+
+    const GLIDER: &str = "#N Glider\n#C A comment\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n";
+
+    #[test]
+    fn parses_glider() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.comments, ["A comment"]);
+        assert_eq!(pattern.rule, Some(Rule::conway()));
+        assert_eq!(pattern.size, (3, 3));
+        let alive: Vec<(usize, usize)> = pattern.cells.iter().map(|(pos, _)| *pos).collect();
+        assert_eq!(alive, [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn round_trips_through_to_string() {
+        assert_eq!(
+            to_string(&parse(GLIDER).unwrap()),
+            "#N Glider\n#C A comment\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+        //runs, empty rows and lines longer than the maximum line length
+        let mut pattern = Pattern::default();
+        let mut cells: Vec<(isize, isize)> =
+            (0..100).filter(|x| x % 7 < 3).map(|x| (x, 0)).collect();
+        cells.push((0, 3));
+        pattern.set_cells(&cells);
+        let text = to_string(&pattern);
+        assert!(text.lines().all(|line| line.len() <= MAXLINELENGTH));
+        let reparsed = parse(&text).unwrap();
+        assert_eq!(reparsed.cells, pattern.cells);
+        assert_eq!(reparsed.size, pattern.size);
+    }
+
+    #[test]
+    fn round_trips_through_the_field() {
+        let text = "#CXRLE Pos=-5,7 Gen=12\nx = 4, y = 3, rule = B2/S/C4:T20,30\n.AB$3C$A2.A!\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.generation, Some(12));
+        assert_eq!(pattern.origin, Some((-5, 7)));
+        assert_eq!(pattern.topology, Some(Topology::Torus(20, 30)));
+        assert!(pattern.cells.contains(&((2, 0), 2)));
+
+        let mut field = Field::new(8);
+        //the pattern reaches beyond the torus, which starts at (0, 0)
+        pattern.load_into(&mut field);
+        let text = to_string(&Pattern::from_field(&field));
+        let reparsed = parse(&text).unwrap();
+        assert_eq!(reparsed.rule, pattern.rule);
+        assert_eq!(reparsed.topology, pattern.topology);
+        assert_eq!(to_string(&reparsed), text);
+    }
+
+    #[test]
+    fn writes_golly_state_letters() {
+        let pattern = Pattern {
+            cells: vec![((1, 0), 1), ((2, 0), 24), ((3, 0), 25), ((4, 0), 255)],
+            size: (5, 1),
+            ..Default::default()
+        };
+        let text = to_string(&pattern);
+        assert!(text.ends_with(".AXpAyO!\n"), "{text}");
+        assert_eq!(parse(&text).unwrap().cells, pattern.cells);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(parse("x = 1, y = 1\nbz!").is_err());
+        assert!(parse("x = 1, y = 1, rule = B3/S23/X\no!").is_err());
+        assert!(parse("x = 1, y = 1\nyX!").is_err());
+        //the header is required
+        assert!(parse("bo$2bo$3o!").is_err());
+        //runs beyond the size in the header and counts that would overflow
+        assert!(parse("x = 3, y = 1\n4o!").is_err());
+        assert!(parse("x = 3, y = 1\no$o!").is_err());
+        assert!(parse("x = 3, y = 1\n99999999999999999999999o!").is_err());
+    }
+
+    #[test]
+    fn keeps_huge_sizes_sparse() {
+        let pattern = parse("x = 1000000000, y = 1000000000\no999999998bo!").unwrap();
+        assert_eq!(pattern.size, (1000000000, 1000000000));
+        assert_eq!(pattern.cells, [((0, 0), 1), ((999999999, 0), 1)]);
+    }
+
+    #[test]
+    fn parses_non_ascii_comments() {
+        let pattern = parse("#é\n#Cé\n#N Glïder\nx = 1, y = 1\no!").unwrap();
+        assert_eq!(pattern.comments, ["é"]);
+        assert_eq!(pattern.name.as_deref(), Some("Glïder"));
+        assert!(parse("#\nx = 1, y = 1\no!").is_err());
+    }
+}
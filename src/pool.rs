@@ -0,0 +1,222 @@
+use std::any::Any;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+
+type Job = Arc<dyn Fn(usize) + Send + Sync>;
+
+//the workers that have not finished the current job yet, and the panic of the first one that failed
+#[derive(Default)]
+struct Progress {
+    pending: usize,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+//amount of items a worker takes from its own queue at once
+const BATCHSIZE: usize = 16;
+//below this many items the work is done on the calling thread
+const MINPARALLELITEMS: usize = 4 * BATCHSIZE;
+
+//long lived worker threads, every worker gets an equal share of the items and steals from the others when done
+pub struct WorkerPool {
+    senders: Vec<Sender<Job>>,
+    progress: Arc<(Mutex<Progress>, Condvar)>,
+    //only one job runs at a time, even if the pool is shared between threads
+    running: Mutex<()>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let progress: Arc<(Mutex<Progress>, Condvar)> = Arc::default();
+        let mut senders = Vec::with_capacity(threads);
+        let mut handles = Vec::with_capacity(threads);
+
+        for index in 0..threads {
+            let (sender, receiver) = channel::<Job>();
+            let progress = progress.clone();
+
+            handles.push(thread::spawn(move || {
+                for job in receiver {
+                    //a panicking job is reported to the caller, the worker stays available
+                    let res = panic::catch_unwind(AssertUnwindSafe(|| job(index)));
+                    //the job has to be dropped before reporting back, so the caller holds the last reference
+                    drop(job);
+                    let (lock, finished) = &*progress;
+                    let mut progress = lock.lock().unwrap_or_else(PoisonError::into_inner);
+                    if let Err(payload) = res {
+                        progress.panic.get_or_insert(payload);
+                    }
+                    progress.pending -= 1;
+                    finished.notify_all();
+                }
+            }));
+            senders.push(sender);
+        }
+
+        Self {
+            senders,
+            progress,
+            running: Mutex::new(()),
+            handles,
+        }
+    }
+
+    pub fn with_available_parallelism() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.senders.len()
+    }
+
+    //runs the job on every worker and waits for all of them, a panic in a job is resumed on the calling thread
+    fn run(&self, job: Job) {
+        let running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let (lock, finished) = &*self.progress;
+        lock.lock().unwrap_or_else(PoisonError::into_inner).pending = self.threads();
+        for sender in &self.senders {
+            sender.send(job.clone()).expect("worker thread died");
+        }
+        drop(job);
+
+        let mut progress = finished
+            .wait_while(
+                lock.lock().unwrap_or_else(PoisonError::into_inner),
+                |progress| progress.pending > 0,
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        let payload = progress.panic.take();
+        drop(progress);
+        drop(running);
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    //applies f to every item, the results are in the order of the items regardless of the thread count
+    pub fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + Sync + 'static,
+        R: Send + 'static,
+        F: Fn(&T) -> R + Send + Sync + 'static,
+    {
+        if items.len() < MINPARALLELITEMS || self.threads() == 1 {
+            return items.iter().map(f).collect();
+        }
+
+        let threads = self.threads();
+        let len = items.len();
+        let queues: Arc<Vec<Mutex<Range<usize>>>> = Arc::new(
+            (0..threads)
+                .map(|i| Mutex::new(i * len / threads..(i + 1) * len / threads))
+                .collect(),
+        );
+        let results = Arc::new(Mutex::new(Vec::with_capacity(len)));
+
+        let job: Job = {
+            let items = Arc::new(items);
+            let queues = queues.clone();
+            let results = results.clone();
+
+            Arc::new(move |index| {
+                let mut res = Vec::new();
+                while let Some(range) = Self::claim(&queues, index) {
+                    for i in range {
+                        res.push((i, f(&items[i])));
+                    }
+                }
+                results.lock().unwrap().extend(res);
+            })
+        };
+        self.run(job);
+
+        let mut results = Arc::try_unwrap(results)
+            .ok()
+            .expect("all workers are done")
+            .into_inner()
+            .unwrap();
+        results.sort_unstable_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
+
+    //takes a batch from the own queue, refilling it with half of the remaining items of another one if empty
+    fn claim(queues: &[Mutex<Range<usize>>], index: usize) -> Option<Range<usize>> {
+        loop {
+            {
+                let mut own = queues[index].lock().unwrap();
+                if !own.is_empty() {
+                    let end = (own.start + BATCHSIZE).min(own.end);
+                    let batch = own.start..end;
+                    own.start = end;
+                    return Some(batch);
+                }
+            }
+            let stolen = (1..queues.len()).find_map(|offset| {
+                let mut other = queues[(index + offset) % queues.len()].lock().unwrap();
+                if other.is_empty() {
+                    None
+                } else {
+                    let mid = other.end - other.len().div_ceil(2);
+                    let stolen = mid..other.end;
+                    other.end = mid;
+                    Some(stolen)
+                }
+            })?;
+            *queues[index].lock().unwrap() = stolen;
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        //closing the channels ends the worker loops
+        self.senders.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_keeps_the_order_of_the_items() {
+        let items: Vec<usize> = (0..1000).collect();
+        for threads in [1, 2, 5] {
+            let pool = WorkerPool::new(threads);
+            let res = pool.map(items.clone(), |i| i * i);
+            assert_eq!(res, items.iter().map(|i| i * i).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn panics_reach_the_caller() {
+        let pool = WorkerPool::new(3);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.map((0..1000).collect(), |i: &usize| assert_ne!(*i, 500))
+        }));
+        assert!(res.is_err());
+        //the workers survive and the pool stays usable
+        assert_eq!(pool.map((0..100).collect(), |i: &usize| *i).len(), 100);
+    }
+
+    #[test]
+    fn can_be_shared_between_threads() {
+        let pool = WorkerPool::new(2);
+        thread::scope(|scope| {
+            for offset in 0..3 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let res = pool.map((0..500).collect(), move |i: &usize| i + offset);
+                    assert_eq!(res, (offset..500 + offset).collect::<Vec<_>>());
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt;
+use std::str::FromStr;
+
+//the letters of the isotropic neighborhoods of 0 to 4 alive neighbors in hensel notation,
+//5 to 8 neighbors use the letters of the complements of 3 to 0
+const LETTERS: [&str; 5] = ["", "ce", "ceaikn", "ceaiknjqry", "ceaiknjqrytwz"];
+//one neighborhood for every letter, bits 0 to 8 are the 3x3 cells row by row with the cell itself as bit 4
+const NEIGHBORHOODS: [&[u16]; 5] = [
+    &[0],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+const NEIGHBORS: u16 = 0b111_101_111;
+const CELL: u16 = 0b000_010_000;
+
+//isotropic rule in B/S notation, e.g. B3/S23 for conways game of life
+//outer totalistic rules only list neighbor counts, non-totalistic ones restrict them to the neighborhoods
+//of hensel's letters, e.g. B2-a/S12 or B3/S2-i34q
+//generations rules like B2/S/C3 add dying states, cells that don't survive take states 2 to C-1 before they are dead
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rule {
+    //the letters of every neighbor count, bit i stands for the i-th letter
+    birth: [u16; 9],
+    survival: [u16; 9],
+    //a bit for every neighborhood, indexed like NEIGHBORHOODS, set if the cell is alive in the next generation
+    table: [u64; 8],
+    states: u8,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseRuleError {
+    MissingSeparator,
+    InvalidPart(String),
+    InvalidNeighborCount(char),
+    InvalidNeighborhood(u8, char),
+    InvalidStates(String),
+    BirthOnZero,
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRuleError::MissingSeparator => {
+                write!(f, "rule has to be of the form B.../S... or B.../S.../C...")
+            }
+            ParseRuleError::InvalidPart(part) => write!(f, "invalid rule part \"{part}\""),
+            ParseRuleError::InvalidNeighborCount(c) => {
+                write!(f, "invalid neighbor count '{c}', expected 0-8")
+            }
+            ParseRuleError::InvalidNeighborhood(n, c) => match Rule::letters(*n) {
+                "" => write!(f, "invalid neighborhood '{c}', {n} neighbors have none"),
+                letters => write!(
+                    f,
+                    "invalid neighborhood '{c}' for {n} neighbors, expected one of {letters}"
+                ),
+            },
+            ParseRuleError::InvalidStates(part) => {
+                write!(f, "invalid number of states \"{part}\", expected 2-255")
+            }
+            ParseRuleError::BirthOnZero => {
+                write!(f, "rules with B0 are not supported on an infinite field")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseRuleError {}
+
+impl Rule {
+    //outer totalistic rule with two states from the neighbor counts of birth and survival
+    pub fn new(birth: &[u8], survival: &[u8]) -> Result<Self, ParseRuleError> {
+        let mut counts = [[0; 9]; 2];
+        for (counts, neighbors) in counts.iter_mut().zip([birth, survival]) {
+            for n in neighbors {
+                let letters = counts
+                    .get_mut(*n as usize)
+                    .ok_or_else(|| ParseRuleError::InvalidPart(n.to_string()))?;
+                *letters = Self::all_letters(*n);
+            }
+        }
+        Self::from_letters(counts[0], counts[1], 2)
+    }
+
+    pub fn conway() -> Self {
+        Self::new(&[3], &[2, 3]).expect("conway's rule is valid")
+    }
+
+    //every rule is built here, which makes sure it can be evaluated
+    fn from_letters(
+        birth: [u16; 9],
+        survival: [u16; 9],
+        states: u8,
+    ) -> Result<Self, ParseRuleError> {
+        //empty chunks are never evaluated, so cells can't be born without neighbors
+        if birth[0] != 0 {
+            return Err(ParseRuleError::BirthOnZero);
+        }
+        let mut table = [0; 8];
+        for (n, (birth, survival)) in birth.iter().zip(&survival).enumerate() {
+            for (letter, neighborhood) in Self::neighborhoods(n as u8).enumerate() {
+                for symmetric in Self::symmetries(neighborhood) {
+                    for (neighborhood, letters) in
+                        [(symmetric, *birth), (symmetric | CELL, *survival)]
+                    {
+                        table[neighborhood as usize / 64] |=
+                            (letters as u64 >> letter & 1) << (neighborhood % 64);
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            birth,
+            survival,
+            table,
+            states,
+        })
+    }
+
+    fn letters(neighbors: u8) -> &'static str {
+        LETTERS[neighbors.min(8 - neighbors) as usize]
+    }
+
+    //a bit for every letter, 0 and 8 neighbors have a single neighborhood without one
+    fn all_letters(neighbors: u8) -> u16 {
+        (1 << Self::letters(neighbors).len().max(1)) - 1
+    }
+
+    fn neighborhoods(neighbors: u8) -> impl Iterator<Item = u16> {
+        let flip = neighbors > 4;
+        NEIGHBORHOODS[neighbors.min(8 - neighbors) as usize]
+            .iter()
+            .map(move |n| if flip { n ^ NEIGHBORS } else { *n })
+    }
+
+    //the neighborhood in all eight rotations and reflections
+    fn symmetries(neighborhood: u16) -> impl Iterator<Item = u16> {
+        let map = move |f: fn(usize, usize) -> (usize, usize)| {
+            (0..9)
+                .filter(|i| neighborhood >> i & 1 == 1)
+                .map(|i| {
+                    let (x, y) = f(i % 3, i / 3);
+                    1 << (x + 3 * y)
+                })
+                .fold(0, |res, bit| res | bit)
+        };
+        [
+            map(|x, y| (x, y)),
+            map(|x, y| (2 - y, x)),
+            map(|x, y| (2 - x, 2 - y)),
+            map(|x, y| (y, 2 - x)),
+            map(|x, y| (2 - x, y)),
+            map(|x, y| (x, 2 - y)),
+            map(|x, y| (y, x)),
+            map(|x, y| (2 - y, 2 - x)),
+        ]
+        .into_iter()
+    }
+
+    //the state of a cell in the next generation by its 3x3 neighborhood, bits as in NEIGHBORHOODS
+    pub fn next_cell(&self, neighborhood: u16) -> bool {
+        let neighborhood = neighborhood as usize & 511;
+        self.table[neighborhood / 64] >> (neighborhood % 64) & 1 == 1
+    }
+
+    //whether every neighborhood of the count gives birth
+    pub fn birth(&self, neighbors: u8) -> bool {
+        self.birth[neighbors as usize] == Self::all_letters(neighbors)
+    }
+
+    pub fn survival(&self, neighbors: u8) -> bool {
+        self.survival[neighbors as usize] == Self::all_letters(neighbors)
+    }
+
+    //whether the next state only depends on the number of neighbors
+    pub fn is_totalistic(&self) -> bool {
+        (0..=8u8).all(|n| {
+            let all = Self::all_letters(n);
+            [self.birth[n as usize], self.survival[n as usize]]
+                .iter()
+                .all(|letters| *letters == 0 || *letters == all)
+        })
+    }
+
+    //2 for alive and dead only, more for generations rules
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    //C3, G3 or just 3
+    fn parse_states(part: &str) -> Result<u8, ParseRuleError> {
+        let digits = part.strip_prefix(['C', 'c', 'G', 'g']).unwrap_or(part);
+        match digits.parse() {
+            Ok(states) if states >= 2 => Ok(states),
+            _ => Err(ParseRuleError::InvalidStates(part.to_string())),
+        }
+    }
+
+    //neighbor counts, each optionally followed by the letters it is restricted to or a '-' and the ones it excludes
+    fn parse_counts(part: &str) -> Result<[u16; 9], ParseRuleError> {
+        let mut counts = [0; 9];
+        let mut chars = part.chars().peekable();
+        while let Some(c) = chars.next() {
+            let n = match c.to_digit(10) {
+                Some(n) if n <= 8 => n as u8,
+                _ => return Err(ParseRuleError::InvalidNeighborCount(c)),
+            };
+            let exclude = chars.next_if_eq(&'-').is_some();
+            let mut letters = 0;
+            while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                match Self::letters(n).find(c.to_ascii_lowercase()) {
+                    Some(i) => letters |= 1 << i,
+                    None => return Err(ParseRuleError::InvalidNeighborhood(n, c)),
+                }
+            }
+            counts[n as usize] |= match (exclude, letters) {
+                (false, 0) => Self::all_letters(n),
+                (false, letters) => letters,
+                (true, 0) => return Err(ParseRuleError::InvalidPart(part.to_string())),
+                (true, letters) => Self::all_letters(n) & !letters,
+            };
+        }
+        Ok(counts)
+    }
+
+    //the shorter of the included and the excluded letters, nothing if all are included
+    fn write_counts(f: &mut fmt::Formatter<'_>, counts: &[u16; 9]) -> fmt::Result {
+        for (n, letters) in (0..=8u8).zip(counts) {
+            if *letters == 0 {
+                continue;
+            }
+            write!(f, "{n}")?;
+            let all = Self::all_letters(n);
+            if *letters == all {
+                continue;
+            }
+            let (prefix, letters) = if letters.count_ones() * 2 <= all.count_ones() {
+                ("", *letters)
+            } else {
+                ("-", all & !letters)
+            };
+            write!(f, "{prefix}")?;
+            for (i, c) in Self::letters(n).chars().enumerate() {
+                if letters >> i & 1 == 1 {
+                    write!(f, "{c}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::conway()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.trim().split('/').collect();
+        let (first, second, states) = match parts[..] {
+            [first, second] => (first, second, 2),
+            [first, second, states] => (first, second, Self::parse_states(states.trim())?),
+            _ => return Err(ParseRuleError::MissingSeparator),
+        };
+
+        let mut birth = None;
+        let mut survival = None;
+
+        for part in [first, second] {
+            let part = part.trim();
+            if let Some(counts) = part.strip_prefix(['B', 'b']) {
+                if birth.is_some() {
+                    return Err(ParseRuleError::InvalidPart(part.to_string()));
+                }
+                birth = Some(Self::parse_counts(counts)?);
+            } else if let Some(counts) = part.strip_prefix(['S', 's']) {
+                if survival.is_some() {
+                    return Err(ParseRuleError::InvalidPart(part.to_string()));
+                }
+                survival = Some(Self::parse_counts(counts)?);
+            } else if birth.is_none() && survival.is_none() {
+                //traditional S/B notation without letters, e.g. 23/3 or 345/2/4 for star wars
+                survival = Some(Self::parse_counts(part)?);
+                birth = Some(Self::parse_counts(second.trim())?);
+                break;
+            } else {
+                return Err(ParseRuleError::InvalidPart(part.to_string()));
+            }
+        }
+
+        match (birth, survival) {
+            (Some(birth), Some(survival)) => Self::from_letters(birth, survival, states),
+            _ => Err(ParseRuleError::MissingSeparator),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "B")?;
+        Self::write_counts(f, &self.birth)?;
+        write!(f, "/S")?;
+        Self::write_counts(f, &self.survival)?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        s.parse().unwrap_or_else(|err| panic!("{s}: {err}"))
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for (text, display) in [
+            ("B3/S23", "B3/S23"),
+            ("b36/s23", "B36/S23"),
+            ("S23/B3", "B3/S23"),
+            ("23/3", "B3/S23"),
+            ("B2/S", "B2/S"),
+            ("345/2/4", "B2/S345/C4"),
+            ("B2/S/C3", "B2/S/C3"),
+            ("B2-a/S12", "B2-a/S12"),
+            ("B2cekin/S12", "B2-a/S12"),
+            ("B2ce/S", "B2ce/S"),
+            ("B3/S2-i34q", "B3/S2-i34q"),
+            ("B2a3-jqry/S1c1e", "B2a3-jqry/S1"),
+            ("B4aceikn/S", "B4ceaikn/S"),
+            ("B4aceiknj/S", "B4-qrytwz/S"),
+            ("B2-a/S12/C4", "B2-a/S12/C4"),
+        ] {
+            let parsed = rule(text);
+            assert_eq!(parsed.to_string(), display, "{text}");
+            assert_eq!(rule(display), parsed);
+        }
+        assert_eq!(rule("B2cekain/S"), rule("B2/S"));
+        assert_eq!(Rule::new(&[3], &[2, 3]), Ok(Rule::conway()));
+        assert_eq!(Rule::new(&[3, 6], &[2, 3]), Ok(rule("B36/S23")));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for (text, err) in [
+            ("B3S23", ParseRuleError::MissingSeparator),
+            ("B3/S9", ParseRuleError::InvalidNeighborCount('9')),
+            ("B3/X23", ParseRuleError::InvalidPart("X23".to_string())),
+            ("B0/S", ParseRuleError::BirthOnZero),
+            ("B1a/S", ParseRuleError::InvalidNeighborhood(1, 'a')),
+            ("B8c/S", ParseRuleError::InvalidNeighborhood(8, 'c')),
+            ("B2-/S", ParseRuleError::InvalidPart("2-".to_string())),
+            ("B2/S/C1", ParseRuleError::InvalidStates("C1".to_string())),
+            (
+                "B2/S/C256",
+                ParseRuleError::InvalidStates("C256".to_string()),
+            ),
+        ] {
+            assert_eq!(text.parse::<Rule>(), Err(err), "{text}");
+        }
+        assert_eq!(
+            Rule::new(&[0, 3], &[2, 3]),
+            Err(ParseRuleError::BirthOnZero)
+        );
+        assert_eq!(
+            Rule::new(&[3], &[2, 9]),
+            Err(ParseRuleError::InvalidPart("9".to_string()))
+        );
+        assert!(Rule::new(&[255], &[]).is_err());
+    }
+
+    #[test]
+    fn totalistic_rules_only_count_neighbors() {
+        let conway = Rule::conway();
+        assert!(conway.is_totalistic());
+        assert!(!rule("B2-a/S12").is_totalistic());
+        for neighborhood in 0..512u16 {
+            let neighbors = (neighborhood & NEIGHBORS).count_ones();
+            let expected = neighbors == 3 || (neighbors == 2 && neighborhood & CELL != 0);
+            assert_eq!(
+                conway.next_cell(neighborhood),
+                expected,
+                "{neighborhood:09b}"
+            );
+        }
+    }
+
+    #[test]
+    fn letters_partition_the_neighborhoods() {
+        for n in 1..=8u8 {
+            let mut classes = [0; 512];
+            //8 neighbors have a single neighborhood without a letter, B0 is not supported at all
+            let letters: Vec<String> = match Rule::letters(n) {
+                "" => vec![String::new()],
+                letters => letters.chars().map(String::from).collect(),
+            };
+            for letter in letters {
+                let birth = rule(&format!("B{n}{letter}/S"));
+                for neighborhood in (0..512u16).filter(|i| birth.next_cell(*i)) {
+                    assert_eq!(neighborhood & CELL, 0);
+                    assert_eq!(neighborhood.count_ones(), n as u32, "{n}{letter}");
+                    for symmetric in Rule::symmetries(neighborhood) {
+                        assert!(birth.next_cell(symmetric), "{n}{letter} {symmetric:09b}");
+                    }
+                    classes[neighborhood as usize] += 1;
+                }
+            }
+            for neighborhood in (0..512u16).filter(|i| i & CELL == 0) {
+                let expected = (neighborhood.count_ones() == n as u32) as u8;
+                assert_eq!(
+                    classes[neighborhood as usize], expected,
+                    "{n} {neighborhood:09b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn letters_match_hensel_shapes() {
+        //bits 0 to 8 are the 3x3 cells row by row
+        for (letters, neighborhood) in [
+            ("2a", 0b000_000_011),
+            ("2i", 0b010_000_010),
+            ("2n", 0b100_000_001),
+            ("2k", 0b100_001_000),
+            ("3i", 0b000_000_111),
+            ("3y", 0b001_100_001),
+            ("4t", 0b010_000_111),
+            ("4z", 0b001_101_100),
+            ("4w", 0b001_001_110),
+            ("5i", 0b000_101_111),
+            ("7c", 0b011_101_111),
+        ] {
+            let birth = rule(&format!("B{letters}/S"));
+            assert!(birth.next_cell(neighborhood), "{letters}");
+            assert!(
+                !rule(&format!("B{}-{}/S", &letters[..1], &letters[1..])).next_cell(neighborhood)
+            );
+        }
+    }
+}
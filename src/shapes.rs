@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::field::Shape;
+use crate::pattern::{Format, PatternError};
+
+//larger files are not read, shapes are meant to be small
+const MAXFILESIZE: u64 = 1 << 20;
+//the most cells the bounding box of a shape can have, since it gets rasterized
+const MAXSHAPECELLS: usize = 1 << 20;
+
+//a shape file that could not be loaded, the path is kept to tell the user which one
+#[derive(Debug)]
+pub enum ShapeError {
+    Io(PathBuf, io::Error),
+    Pattern(PathBuf, PatternError),
+    NotUtf8(PathBuf),
+    TooLarge(PathBuf),
+    Empty(PathBuf),
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            ShapeError::Pattern(path, err) => write!(f, "{}: {err}", path.display()),
+            ShapeError::NotUtf8(path) => write!(f, "{}: not a text file", path.display()),
+            ShapeError::TooLarge(path) => write!(f, "{}: too large for a shape", path.display()),
+            ShapeError::Empty(path) => write!(f, "{}: contains no cells", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShapeError::Io(_, err) => Some(err),
+            ShapeError::Pattern(_, err) => Some(err),
+            ShapeError::NotUtf8(_) | ShapeError::TooLarge(_) | ShapeError::Empty(_) => None,
+        }
+    }
+}
+
+pub fn load_shape(path: &Path) -> Result<Shape, ShapeError> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)
+        .and_then(|file| file.take(MAXFILESIZE + 1).read_to_end(&mut bytes))
+        .map_err(|err| ShapeError::Io(path.to_path_buf(), err))?;
+    if bytes.len() as u64 > MAXFILESIZE {
+        return Err(ShapeError::TooLarge(path.to_path_buf()));
+    }
+    let text = std::str::from_utf8(&bytes).map_err(|_| ShapeError::NotUtf8(path.to_path_buf()))?;
+
+    let pattern = Format::detect(path, text)
+        .parse(&bytes)
+        .map_err(|err| match err {
+            PatternError::Io(err) => ShapeError::Io(path.to_path_buf(), err),
+            err => ShapeError::Pattern(path.to_path_buf(), err),
+        })?;
+    if pattern.size.0.saturating_mul(pattern.size.1) > MAXSHAPECELLS {
+        return Err(ShapeError::TooLarge(path.to_path_buf()));
+    }
+    let shape = pattern.to_shape();
+
+    if shape.iter().flatten().all(Option::is_none) {
+        return Err(ShapeError::Empty(path.to_path_buf()));
+    }
+    Ok(shape)
+}
+
+type FileStamp = (Option<SystemTime>, u64);
+
+//a file in one of the directories, with its place in the menu and its modification time and size
+#[derive(PartialEq, Eq)]
+struct ScannedFile {
+    menupath: Vec<String>,
+    stamp: FileStamp,
+}
+
+//the shapes of some directories, files are parsed again only when they change
+pub struct ShapeLibrary {
+    dirs: Vec<PathBuf>,
+    files: HashMap<PathBuf, ScannedFile>,
+    shapes: Vec<ShapeFile>,
+}
+
+impl ShapeLibrary {
+    pub fn load(dirs: Vec<PathBuf>) -> (Self, Vec<ShapeError>) {
+        let mut library = Self {
+            dirs,
+            files: HashMap::new(),
+            shapes: Vec::new(),
+        };
+        let errors = library.reload();
+        (library, errors)
+    }
+
+    pub fn shapes(&self) -> &[ShapeFile] {
+        &self.shapes
+    }
+
+    pub fn get(&self, menupath: &[String]) -> Option<&ShapeFile> {
+        self.shapes.iter().find(|file| file.menupath == menupath)
+    }
+
+    //only looks at the metadata, a file counts as changed if its modification time or size differs
+    pub fn has_changed(&self) -> bool {
+        scan_dirs(&self.dirs).0 != self.files
+    }
+
+    //parses the files that were added or changed since the last scan and drops the removed ones, the errors are only those of the parsed files
+    pub fn reload(&mut self) -> Vec<ShapeError> {
+        let (files, mut errors) = scan_dirs(&self.dirs);
+        let old = std::mem::replace(&mut self.files, files);
+
+        self.shapes
+            .retain(|shape| old.get(&shape.path) == self.files.get(&shape.path));
+        for (path, file) in &self.files {
+            if old.get(path) == Some(file) {
+                continue;
+            }
+            match load_shape(path) {
+                Ok(shape) => self.shapes.push(ShapeFile {
+                    menupath: file.menupath.clone(),
+                    path: path.clone(),
+                    shape,
+                }),
+                Err(err) => errors.push(err),
+            }
+        }
+        self.shapes.sort_by(|a, b| a.menupath.cmp(&b.menupath));
+        errors
+    }
+}
+
+//a loaded shape and where it shows up in the menu, one entry per subdirectory and the name last
+pub struct ShapeFile {
+    pub menupath: Vec<String>,
+    pub path: PathBuf,
+    pub shape: Shape,
+}
+
+//every file of the directories and their subdirectories, entries that can't be read are skipped and their errors returned
+fn scan_dirs(dirs: &[PathBuf]) -> (HashMap<PathBuf, ScannedFile>, Vec<ShapeError>) {
+    let mut files = HashMap::new();
+    let mut errors = Vec::new();
+    for dir in dirs {
+        scan_dir(dir, &mut Vec::new(), &mut files, &mut errors);
+    }
+    (files, errors)
+}
+
+fn scan_dir(
+    dir: &Path,
+    menupath: &mut Vec<String>,
+    files: &mut HashMap<PathBuf, ScannedFile>,
+    errors: &mut Vec<ShapeError>,
+) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            errors.push(ShapeError::Io(dir.to_path_buf(), err));
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                errors.push(ShapeError::Io(dir.to_path_buf(), err));
+                continue;
+            }
+        };
+        let path = entry.path();
+        //names that are not valid utf-8 are still shown, just not exactly
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        //symlinked directories are not followed, they could form a loop
+        if entry.file_type().is_ok_and(|filetype| filetype.is_dir()) {
+            menupath.push(name);
+            scan_dir(&path, menupath, files, errors);
+            menupath.pop();
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => continue,
+            Err(err) => {
+                errors.push(ShapeError::Io(path, err));
+                continue;
+            }
+        };
+
+        let mut filemenupath = menupath.clone();
+        filemenupath.push(name);
+        files.insert(
+            path,
+            ScannedFile {
+                menupath: filemenupath,
+                stamp: (metadata.modified().ok(), metadata.len()),
+            },
+        );
+    }
+}
+
+//the label of a menu item, fltk treats / as the separator of submenus, a leading _ as divider and & as shortcut marker
+pub fn menu_label(menupath: &[String]) -> String {
+    menupath
+        .iter()
+        .map(|name| {
+            name.replace('\\', "\\\\")
+                .replace('/', "\\/")
+                .replace('_', "\\_")
+                .replace('&', "&&")
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a fresh directory for each test, removed again when the test is done
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("game-of-life-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn menupaths(library: &ShapeLibrary) -> Vec<String> {
+        library
+            .shapes()
+            .iter()
+            .map(|file| file.menupath.join("/"))
+            .collect()
+    }
+
+    #[test]
+    fn reloads_only_changed_files() {
+        let dir = TempDir::new("reload");
+        let glider = dir.0.join("glider");
+        fs::write(&glider, "010\n001\n111\n").unwrap();
+        fs::write(dir.0.join("block"), "11\n11\n").unwrap();
+        fs::write(dir.0.join("broken.rle"), "x = 1, y = 1\nz!\n").unwrap();
+
+        let (mut library, errors) = ShapeLibrary::load(vec![dir.0.clone()]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(menupaths(&library), ["block", "glider"]);
+        assert!(!library.has_changed());
+
+        //the same size and modification time, so the new content is not noticed
+        let modified = fs::metadata(&glider).unwrap().modified().unwrap();
+        fs::write(&glider, "111\n100\n010\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&glider)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::remove_file(dir.0.join("block")).unwrap();
+        fs::create_dir(dir.0.join("oscillators")).unwrap();
+        fs::write(dir.0.join("oscillators").join("blinker"), "111\n").unwrap();
+        assert!(library.has_changed());
+
+        //the broken file did not change, so it is not reported again
+        assert!(library.reload().is_empty());
+        assert_eq!(menupaths(&library), ["glider", "oscillators/blinker"]);
+        let shape = &library.get(&["glider".to_string()]).unwrap().shape;
+        assert_eq!(shape[0], [Some(0), Some(0), Some(1), None]);
+
+        fs::write(&glider, "0111\n").unwrap();
+        assert!(library.reload().is_empty());
+        let shape = &library.get(&["glider".to_string()]).unwrap().shape;
+        assert_eq!(shape.len(), 4);
+    }
+
+    #[test]
+    fn skips_and_reports_broken_files() {
+        let dir = TempDir::new("broken");
+        fs::create_dir(dir.0.join("ships")).unwrap();
+        fs::write(dir.0.join("ships").join("glider"), "010\n001\n111\n").unwrap();
+        fs::write(dir.0.join("malformed.rle"), "x = 1, y = 1\nz!\n").unwrap();
+        fs::write(dir.0.join("binary"), [0xff, 0xfe, b'1', b'\n']).unwrap();
+        fs::write(dir.0.join("huge.rle"), "x = 100000, y = 100000\no!\n").unwrap();
+        fs::write(dir.0.join("long"), vec![b'1'; MAXFILESIZE as usize + 1]).unwrap();
+
+        let (library, errors) = ShapeLibrary::load(vec![dir.0.clone()]);
+        let menupaths: Vec<&[String]> = library
+            .shapes()
+            .iter()
+            .map(|file| &file.menupath[..])
+            .collect();
+        assert_eq!(menupaths, [["ships".to_string(), "glider".to_string()]]);
+
+        let mut reported: Vec<(String, &str)> = errors
+            .iter()
+            .map(|err| match err {
+                ShapeError::Pattern(path, _) => (path, "pattern"),
+                ShapeError::NotUtf8(path) => (path, "utf-8"),
+                ShapeError::TooLarge(path) => (path, "size"),
+                ShapeError::Io(path, _) | ShapeError::Empty(path) => (path, "other"),
+            })
+            .map(|(path, kind)| {
+                (
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    kind,
+                )
+            })
+            .collect();
+        reported.sort();
+        assert_eq!(
+            reported,
+            [
+                ("binary".to_string(), "utf-8"),
+                ("huge.rle".to_string(), "size"),
+                ("long".to_string(), "size"),
+                ("malformed.rle".to_string(), "pattern"),
+            ]
+        );
+    }
+}
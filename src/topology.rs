@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+//the shape of the universe, finite universes span the cells (0, 0) to (width - 1, height - 1)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Topology {
+    #[default]
+    Plane,
+    //opposite edges are joined
+    Torus(usize, usize),
+    //everything beyond the edges is dead
+    Bounded(usize, usize),
+    //one pair of edges is joined like a torus and the other with a half twist,
+    //top and bottom edge are twisted unless the last value says the left and right edge are
+    KleinBottle(usize, usize, bool),
+    //both pairs of edges are joined with a half twist
+    CrossSurface(usize, usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseTopologyError {
+    InvalidKind(String),
+    InvalidSize(String),
+}
+
+impl fmt::Display for ParseTopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTopologyError::InvalidKind(kind) => write!(
+                f,
+                "invalid topology \"{kind}\", expected T (torus), P (bounded plane), K (klein bottle) or C (cross-surface)"
+            ),
+            ParseTopologyError::InvalidSize(size) => {
+                write!(f, "invalid size \"{size}\", expected <width>,<height> above 0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTopologyError {}
+
+impl Topology {
+    pub fn size(&self) -> Option<(usize, usize)> {
+        match *self {
+            Topology::Plane => None,
+            Topology::Torus(w, h)
+            | Topology::Bounded(w, h)
+            | Topology::KleinBottle(w, h, _)
+            | Topology::CrossSurface(w, h) => Some((w, h)),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.size().is_some()
+    }
+
+    pub fn contains(&self, coords: (isize, isize)) -> bool {
+        match self.size() {
+            Some((w, h)) => {
+                (0..w as isize).contains(&coords.0) && (0..h as isize).contains(&coords.1)
+            }
+            None => true,
+        }
+    }
+
+    //the cell of the universe a neighbor beyond the edges stands for, None if it is always dead
+    pub fn wrap(&self, coords: (isize, isize)) -> Option<(isize, isize)> {
+        let (x, y) = coords;
+        match *self {
+            Topology::Plane => Some(coords),
+            Topology::Torus(w, h) => Some((x.rem_euclid(w as isize), y.rem_euclid(h as isize))),
+            Topology::Bounded(..) => self.contains(coords).then_some(coords),
+            Topology::KleinBottle(w, h, sides) => {
+                let (w, h) = (w as isize, h as isize);
+                let (x, y) = match sides {
+                    false if !(0..h).contains(&y) => (w - 1 - x, y),
+                    true if !(0..w).contains(&x) => (x, h - 1 - y),
+                    _ => (x, y),
+                };
+                Some((x.rem_euclid(w), y.rem_euclid(h)))
+            }
+            Topology::CrossSurface(w, h) => {
+                let (w, h) = (w as isize, h as isize);
+                match ((0..w).contains(&x), (0..h).contains(&y)) {
+                    (true, true) => Some(coords),
+                    (true, false) => Some((w - 1 - x, y.rem_euclid(h))),
+                    (false, true) => Some((x.rem_euclid(w), h - 1 - y)),
+                    //the diagonal neighbors beyond a corner would be the cell itself
+                    (false, false) => None,
+                }
+            }
+        }
+    }
+}
+
+//golly's notation, e.g. T64,64 for a torus, the infinite plane has none
+impl FromStr for Topology {
+    type Err = ParseTopologyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("plane") {
+            return Ok(Topology::Plane);
+        }
+
+        let (kind, size) = s.split_at(s.chars().next().map_or(0, char::len_utf8));
+        let invalid = || ParseTopologyError::InvalidSize(size.to_string());
+        let (w, h) = size.split_once(',').ok_or_else(invalid)?;
+        //golly marks the twisted edges of a klein bottle with a star, the top and bottom ones by default
+        let (w, h, sides) = match kind {
+            "K" | "k" => match h.strip_suffix('*') {
+                Some(h) => (w, h, true),
+                None => (w.strip_suffix('*').unwrap_or(w), h, false),
+            },
+            _ => (w, h, false),
+        };
+        let w: usize = w.trim().parse().map_err(|_| invalid())?;
+        let h: usize = h.trim().parse().map_err(|_| invalid())?;
+        if w == 0 || h == 0 {
+            return Err(invalid());
+        }
+
+        match kind {
+            "T" | "t" => Ok(Topology::Torus(w, h)),
+            "P" | "p" => Ok(Topology::Bounded(w, h)),
+            "K" | "k" => Ok(Topology::KleinBottle(w, h, sides)),
+            "C" | "c" => Ok(Topology::CrossSurface(w, h)),
+            _ => Err(ParseTopologyError::InvalidKind(kind.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Topology::Plane => write!(f, "Plane"),
+            Topology::Torus(w, h) => write!(f, "T{w},{h}"),
+            Topology::Bounded(w, h) => write!(f, "P{w},{h}"),
+            Topology::KleinBottle(w, h, false) => write!(f, "K{w}*,{h}"),
+            Topology::KleinBottle(w, h, true) => write!(f, "K{w},{h}*"),
+            Topology::CrossSurface(w, h) => write!(f, "C{w},{h}"),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::field::{Shape, State};
+
+//the eight symmetries of a square, rotations are clockwise on the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    //along the diagonal from the upper left to the lower right corner
+    Transpose,
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transform::Identity => "Identity",
+            Transform::Rotate90 => "Rotate clockwise",
+            Transform::Rotate180 => "Rotate 180°",
+            Transform::Rotate270 => "Rotate counterclockwise",
+            Transform::FlipHorizontal => "Flip horizontally",
+            Transform::FlipVertical => "Flip vertically",
+            Transform::Transpose => "Flip diagonally",
+            Transform::AntiTranspose => "Flip antidiagonally",
+        }
+    }
+
+    fn swaps_axes(&self) -> bool {
+        matches!(
+            self,
+            Transform::Rotate90
+                | Transform::Rotate270
+                | Transform::Transpose
+                | Transform::AntiTranspose
+        )
+    }
+
+    //the size of a transformed rectangle of the given size
+    pub fn size(&self, (width, height): (usize, usize)) -> (usize, usize) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    //where a cell of a rectangle of the given size ends up, both relative to the upper left corner
+    pub fn map(&self, (x, y): (usize, usize), (width, height): (usize, usize)) -> (usize, usize) {
+        let (right, bottom) = (width - 1 - x, height - 1 - y);
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (bottom, x),
+            Transform::Rotate180 => (right, bottom),
+            Transform::Rotate270 => (y, right),
+            Transform::FlipHorizontal => (right, y),
+            Transform::FlipVertical => (x, bottom),
+            Transform::Transpose => (y, x),
+            Transform::AntiTranspose => (bottom, right),
+        }
+    }
+
+    //columns of different length are treated as filled up with cells that are left unchanged
+    pub fn apply(&self, shape: &Shape) -> Shape {
+        let size = (shape.len(), height(shape));
+        let (width, height) = self.size(size);
+        let mut res = vec![vec![None; height]; width];
+
+        for (x, column) in shape.iter().enumerate() {
+            for (y, item) in column.iter().enumerate() {
+                let (newx, newy) = self.map((x, y), size);
+                res[newx][newy] = *item;
+            }
+        }
+        res
+    }
+}
+
+fn height(shape: &Shape) -> usize {
+    shape.iter().map(|column| column.len()).max().unwrap_or(0)
+}
+
+//the smallest rectangle containing every alive or dying cell and how far its upper left corner is from the old one
+pub fn trim(shape: &Shape) -> (Shape, (usize, usize)) {
+    let alive = |x: usize, y: usize| matches!(shape[x].get(y), Some(Some(state)) if *state != 0);
+    let xs = || (0..shape.len()).filter(|x| (0..shape[*x].len()).any(|y| alive(*x, y)));
+    let ys = || (0..height(shape)).filter(|y| (0..shape.len()).any(|x| alive(x, *y)));
+
+    let (Some(minx), Some(maxx), Some(miny), Some(maxy)) =
+        (xs().next(), xs().next_back(), ys().next(), ys().next_back())
+    else {
+        return (Vec::new(), (0, 0));
+    };
+
+    let res = shape[minx..=maxx]
+        .iter()
+        .map(|column| {
+            (miny..=maxy)
+                .map(|y| column.get(y).copied().flatten())
+                .collect()
+        })
+        .collect();
+    (res, (minx, miny))
+}
+
+//dead cells become alive ones and the others dead, cells that are left unchanged stay that way
+pub fn invert(shape: &Shape) -> Shape {
+    shape
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|item| item.map(|state| State::from(state == 0)))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //3 wide and 2 high without any symmetry, with a cell that is left unchanged
+    fn asymmetric() -> Shape {
+        vec![
+            vec![Some(1), Some(0)],
+            vec![Some(1), None],
+            vec![Some(0), Some(1)],
+        ]
+    }
+
+    fn apply_all(transforms: &[Transform], shape: &Shape) -> Shape {
+        transforms
+            .iter()
+            .fold(shape.clone(), |shape, transform| transform.apply(&shape))
+    }
+
+    #[test]
+    fn rotations_are_clockwise() {
+        //the upper left cell goes to the upper right corner and the lower left one to the upper left
+        let rotated = Transform::Rotate90.apply(&asymmetric());
+        assert_eq!(
+            rotated,
+            vec![
+                vec![Some(0), None, Some(1)],
+                vec![Some(1), Some(1), Some(0)]
+            ]
+        );
+        assert_eq!(
+            apply_all(&[Transform::Rotate90; 2], &asymmetric()),
+            Transform::Rotate180.apply(&asymmetric())
+        );
+        assert_eq!(
+            apply_all(&[Transform::Rotate90; 3], &asymmetric()),
+            Transform::Rotate270.apply(&asymmetric())
+        );
+    }
+
+    #[test]
+    fn inverse_transforms_restore_the_shape() {
+        let shape = asymmetric();
+        assert_eq!(apply_all(&[Transform::Rotate90; 4], &shape), shape);
+        assert_eq!(
+            apply_all(&[Transform::Rotate90, Transform::Rotate270], &shape),
+            shape
+        );
+        for transform in [
+            Transform::Rotate180,
+            Transform::FlipHorizontal,
+            Transform::FlipVertical,
+            Transform::Transpose,
+            Transform::AntiTranspose,
+        ] {
+            assert_eq!(apply_all(&[transform; 2], &shape), shape, "{transform:?}");
+        }
+    }
+
+    #[test]
+    fn transforms_compose_within_the_group() {
+        let shape = asymmetric();
+        let images: Vec<Shape> = Transform::ALL
+            .iter()
+            .map(|transform| transform.apply(&shape))
+            .collect();
+        //the eight symmetries give eight different shapes
+        for (i, image) in images.iter().enumerate() {
+            assert!(!images[..i].contains(image), "{:?}", Transform::ALL[i]);
+        }
+        for a in Transform::ALL {
+            for b in Transform::ALL {
+                let composed = apply_all(&[a, b], &shape);
+                assert!(images.contains(&composed), "{a:?} then {b:?}");
+                assert_eq!(
+                    (composed.len(), composed[0].len()),
+                    b.size(a.size((3, 2))),
+                    "{a:?} then {b:?}"
+                );
+            }
+        }
+        assert_eq!(
+            apply_all(
+                &[Transform::FlipHorizontal, Transform::FlipVertical],
+                &shape
+            ),
+            Transform::Rotate180.apply(&shape)
+        );
+        assert_eq!(
+            apply_all(&[Transform::Transpose, Transform::FlipHorizontal], &shape),
+            Transform::Rotate90.apply(&shape)
+        );
+    }
+
+    #[test]
+    fn invert_round_trips() {
+        let shape = asymmetric();
+        assert_eq!(
+            invert(&shape),
+            vec![
+                vec![Some(0), Some(1)],
+                vec![Some(0), None],
+                vec![Some(1), Some(0)],
+            ]
+        );
+        assert_eq!(invert(&invert(&shape)), shape);
+    }
+
+    #[test]
+    fn trim_round_trips() {
+        let mut shape = vec![vec![Some(0); 5]; 6];
+        shape[2][1] = Some(1);
+        shape[4][3] = Some(3);
+        shape[3][2] = None;
+
+        let (trimmed, (dx, dy)) = trim(&shape);
+        assert_eq!((dx, dy), (2, 1));
+        assert_eq!((trimmed.len(), height(&trimmed)), (3, 3));
+        //placing the trimmed shape back at its offset gives the cells it was cut from
+        for (x, column) in trimmed.iter().enumerate() {
+            for (y, item) in column.iter().enumerate() {
+                assert_eq!(*item, shape[x + dx][y + dy]);
+            }
+        }
+        assert_eq!(trim(&trimmed), (trimmed.clone(), (0, 0)));
+        assert_eq!(trim(&vec![vec![Some(0), None]; 3]), (Vec::new(), (0, 0)));
+    }
+}